@@ -1,19 +1,232 @@
-use crate::neat::genome::DomainConfig;
-use crate::neat::network::{sigmoid, Network, NeuralInterface};
+use crate::neat::genome::{DomainConfig, Genome};
+use crate::neat::network::{Network, NeuralInterface};
 use crate::routes::DIE_PATTERNS;
-use crate::rri::{DrawAction, GameState, RRIAgent, Tile, Turn};
+use crate::rri::{DieFace, DrawAction, GameState, RRIAgent, Tile, Turn, EXITS};
+use crate::scoring::ScoreCard;
+use anyhow::{bail, Result};
 use decorum::R64;
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::{Rng, SeedableRng};
+use std::borrow::BorrowMut;
+use std::collections::HashSet;
+use std::path::Path;
 
-pub struct NeatAgent<'a> {
-    network: &'a mut Network,
+// Baseline: draws each die in a random order, at a random legal placement.
+// Dice only get passed when nothing fits.
+pub struct RandomAgent<R: Rng = StdRng> {
+    rng: R,
+}
+
+impl RandomAgent {
+    pub fn seeded(seed: u64) -> Self {
+        Self::new(StdRng::seed_from_u64(seed))
+    }
+}
+
+impl<R: Rng> RandomAgent<R> {
+    pub fn new(rng: R) -> Self {
+        Self { rng }
+    }
+}
+
+impl<R: Rng> RandomAgent<R> {
+    pub fn random_turn(&mut self, state: &GameState, dice: &[DieFace]) -> Turn {
+        let mut state = state.clone();
+        let mut dice = dice.to_vec();
+        dice.shuffle(&mut self.rng);
+
+        // a die that doesn't fit yet may fit once the others are drawn
+        let mut remaining = dice.to_vec();
+        let mut actions = Vec::with_capacity(4);
+        loop {
+            let count = remaining.len();
+            remaining.retain(|face| {
+                let Some(action) = state.legal_actions(*face).choose(&mut self.rng).copied() else {
+                    return true;
+                };
+                state.apply_route(action).expect("legal action");
+                actions.push(action);
+                false
+            });
+            if remaining.is_empty() || remaining.len() == count {
+                return Turn { actions };
+            }
+        }
+    }
+}
+
+impl<R: Rng> RRIAgent for RandomAgent<R> {
+    fn prompt(&mut self, state: &GameState) -> Turn {
+        self.random_turn(state, &state.dice.faces())
+    }
+}
+
+// Baseline: tries every order and placement of this round's dice,
+// and draws the turn with the best score once it's done.
+pub struct GreedyAgent {
+    pub config: GreedyConfig,
+}
+
+pub struct GreedyConfig {
+    // applied in order, to break ties in the score
+    pub tie_breaks: Vec<TieBreak>,
+}
+
+impl Default for GreedyConfig {
+    fn default() -> Self {
+        Self {
+            tie_breaks: vec![TieBreak::FewerErrors, TieBreak::MoreOpenEdges],
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub enum TieBreak {
+    // fewer route ends leading nowhere
+    FewerErrors,
+    // more places for the next rounds' dice to connect
+    MoreOpenEdges,
+    FewerRoutes,
+}
+
+impl TieBreak {
+    fn value(&self, state: &GameState, card: &ScoreCard) -> isize {
+        match self {
+            TieBreak::FewerErrors => -card.errors,
+            TieBreak::MoreOpenEdges => state.open_edges.len() as isize,
+            TieBreak::FewerRoutes => -(state.drawn_routes.len() as isize),
+        }
+    }
+}
+
+impl Default for GreedyAgent {
+    fn default() -> Self {
+        Self::new(GreedyConfig::default())
+    }
+}
+
+impl GreedyAgent {
+    pub fn new(config: GreedyConfig) -> Self {
+        Self { config }
+    }
+
+    // Score first, then each tie break.
+    fn evaluate(&self, state: &GameState) -> Vec<isize> {
+        let card = ScoreCard::new(state);
+        let mut value = Vec::with_capacity(1 + self.config.tie_breaks.len());
+        value.push(card.total());
+        value.extend(self.config.tie_breaks.iter().map(|t| t.value(state, &card)));
+        value
+    }
+
+    // Best turn for the given dice, in this state.
+    pub fn best_turn(&self, state: &GameState, dice: &[DieFace]) -> Turn {
+        let mut best: Option<(Vec<isize>, Vec<DrawAction>)> = None;
+        every_turn(state, dice, |actions, board| {
+            let value = self.evaluate(board);
+            if best.as_ref().is_none_or(|(best, _)| value > *best) {
+                best = Some((value, actions.to_vec()));
+            }
+        });
+        Turn {
+            actions: best.map_or(vec![], |(_, actions)| actions),
+        }
+    }
+
+    // Best route for any one of the dice, judged without the others.
+    pub fn best_route(&self, state: &GameState, dice: &[DieFace]) -> Option<(usize, DrawAction)> {
+        let dice: Vec<(DieFace, DieFace)> = dice.iter().map(|face| (*face, *face)).collect();
+        best_legal_action(state, &dice, |_, action| {
+            let mut next = state.clone();
+            next.apply_route(*action).expect("legal action");
+            self.evaluate(&next)
+        })
+    }
+}
+
+// Calls back with every way to draw the dice, once per distinct set of routes,
+// and the board it leaves. Dice only get passed when none of the remaining ones fit.
+pub(crate) fn every_turn(
+    state: &GameState,
+    dice: &[DieFace],
+    finish: impl FnMut(&[DrawAction], &GameState),
+) {
+    let mut search = TurnSearch {
+        visited: HashSet::new(),
+        path: Vec::with_capacity(4),
+        finish,
+    };
+    search.visit(state, dice);
+}
+
+struct TurnSearch<F: FnMut(&[DrawAction], &GameState)> {
+    // sets of drawn routes already explored in another order
+    visited: HashSet<Vec<usize>>,
+    path: Vec<DrawAction>,
+    finish: F,
+}
+
+impl<F: FnMut(&[DrawAction], &GameState)> TurnSearch<F> {
+    fn visit(&mut self, state: &GameState, dice: &[DieFace]) {
+        let mut placed_any = false;
+        for (i, face) in dice.iter().enumerate() {
+            // the same face twice gives the same turns
+            if dice[..i].contains(face) {
+                continue;
+            }
+            let mut remaining = dice.to_vec();
+            remaining.remove(i);
+            for action in state.legal_actions(*face) {
+                placed_any = true;
+                self.path.push(action);
+                if self.visited.insert(route_key(&self.path)) {
+                    let mut next = state.clone();
+                    next.apply_route(action).expect("legal action");
+                    self.visit(&next, &remaining);
+                }
+                self.path.pop();
+            }
+        }
+
+        // only passes when none of the remaining dice fit
+        if !placed_any {
+            (self.finish)(&self.path, state);
+        }
+    }
+}
+
+// Identifies a set of drawn routes, regardless of the order they were drawn in.
+pub(crate) fn route_key(actions: &[DrawAction]) -> Vec<usize> {
+    let mut key: Vec<usize> = actions
+        .iter()
+        .map(|action| {
+            let tile = (action.tile.x + action.tile.y * 7) as usize;
+            tile * DIE_PATTERNS.len() + action.pattern.index
+        })
+        .collect();
+    key.sort_unstable();
+    key
+}
+
+impl RRIAgent for GreedyAgent {
+    fn prompt(&mut self, state: &GameState) -> Turn {
+        self.best_turn(state, &state.dice.faces())
+    }
+}
+
+// The network is either borrowed while training, or owned when loaded from a saved genome.
+pub struct NeatAgent<N: BorrowMut<Network>> {
+    network: N,
+    encoder: &'static (dyn StateEncoder + Sync),
+    decoder: &'static (dyn ActionDecoder + Sync),
     score_modifier: isize,
 }
 
-impl<'a> RRIAgent for NeatAgent<'a> {
+impl<N: BorrowMut<Network>> RRIAgent for NeatAgent<N> {
     fn prompt(&mut self, state: &GameState) -> Turn {
-        let input = NeatAgentMethods::to_input(state);
-        let output = self.network.run(&input);
-        NeatAgentMethods::from_output(output)
+        self.decoder
+            .decode(self.network.borrow_mut(), self.encoder, state)
     }
 
     // errors are penalized; but the game doesn't halt
@@ -22,61 +235,69 @@ impl<'a> RRIAgent for NeatAgent<'a> {
     }
 }
 
-impl<'a> NeatAgent<'a> {
-    pub fn new(network: &'a mut Network) -> Self {
+impl NeatAgent<Network> {
+    // Plays a genome saved by training, with the encoder and decoder it was trained with.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let (genome, encoding) = Genome::load(path)?;
+        let Some((encoder, decoder)) = encoding.split_once('/').and_then(|(encoder, decoder)| {
+            let encoder = ENCODERS.iter().find(|e| e.name() == encoder)?;
+            let decoder = DECODERS.iter().find(|d| d.name() == decoder)?;
+            Some((*encoder, *decoder))
+        }) else {
+            bail!("Unknown encoding {}", encoding);
+        };
+        let inputs = encoder.size() + decoder.extra_inputs();
+        if genome.in_nodes != inputs || genome.out_nodes != decoder.size() {
+            bail!(
+                "Genome has {} inputs and {} outputs, {} expects {} and {}",
+                genome.in_nodes,
+                genome.out_nodes,
+                encoding,
+                inputs,
+                decoder.size()
+            );
+        }
+        Ok(Self::new(Network::new(&genome)?, encoder, decoder))
+    }
+}
+
+impl<N: BorrowMut<Network>> NeatAgent<N> {
+    pub fn new(
+        network: N,
+        encoder: &'static (dyn StateEncoder + Sync),
+        decoder: &'static (dyn ActionDecoder + Sync),
+    ) -> Self {
         Self {
             network,
+            encoder,
+            decoder,
             score_modifier: 0,
         }
     }
-    const GAME_COUNT: usize = 100;
 
-    // average score across many games
-    pub fn fitness(&mut self) -> f64 {
-        let mut score = 0_isize;
-        for _ in 0..Self::GAME_COUNT {
-            let mut game = GameState::new();
-            game.play(self);
-            score += game.score();
-        }
-        (score + self.score_modifier) as f64 / Self::GAME_COUNT as f64
+    // the mean score, less the error penalties spread over the games
+    pub fn fitness(&mut self, seeds: &[u64]) -> f64 {
+        let score = mean_score(self, seeds);
+        score + self.score_modifier as f64 / seeds.len().max(1) as f64
     }
 }
 
+// Average score across a game for each seed, the seed rolls the dice.
+pub fn mean_score<A: RRIAgent + ?Sized>(agent: &mut A, seeds: &[u64]) -> f64 {
+    let mut score = 0_isize;
+    for seed in seeds {
+        let mut game = GameState::new();
+        game.play_with(agent, &mut StdRng::seed_from_u64(*seed));
+        score += game.score();
+    }
+    score as f64 / seeds.len().max(1) as f64
+}
+
 pub struct NeatAgentMethods;
 
 impl NeatAgentMethods {
-    // 34 patterns
-    // 49 tiles
-    // 3 regular dice with 6 unique sides
-    // 1 special die with 3 unique sides
-    const INPUT_LAYER_SIZE: usize =
-          34 * 49 // drawn on grid
-        +  3 *  6 // regular dice
-        +  1 *  3 // special die
-    ;
-
     const OUTPUT_LAYER_SIZE: usize = 4 * 49 * 34; // 4 dice patterns placed on the grid.
 
-    pub fn to_input(state: &GameState) -> [f64; Self::INPUT_LAYER_SIZE] {
-        let mut input = [0.0; Self::INPUT_LAYER_SIZE];
-        for action in state.drawn_routes.iter() {
-            let tile_offset = 34 * (action.tile.x + action.tile.y * 7) as usize;
-            input[tile_offset + action.pattern.face as usize] = 1.0;
-        }
-        let mut index: usize = 34 * 49;
-
-        // regular dice:
-        for face in state.dice.regular {
-            input[index + face as usize] = 1.0;
-            index += 6;
-        }
-
-        // special die
-        input[index + (state.dice.special as usize - 6)] = 1.0;
-        input
-    }
-
     pub fn from_output(output: &[f64]) -> Turn {
         let mut actions = Vec::with_capacity(4);
         // largest value in the grid is the placement,
@@ -93,8 +314,8 @@ impl NeatAgentMethods {
             if *pair.1 > 0.5 {
                 let pattern = &DIE_PATTERNS[pair.0 % 34];
                 let tile = pair.0 / 34;
-                let x = (tile / 7) as u8;
-                let y = (tile % 7) as u8;
+                let x = (tile % 7) as u8;
+                let y = (tile / 7) as u8;
                 actions.push(DrawAction {
                     tile: Tile { x, y },
                     pattern,
@@ -104,14 +325,495 @@ impl NeatAgentMethods {
         }
         Turn { actions }
     }
+    // how a genome trained with these is saved, e.g. "rich/masked"
+    pub fn encoding(
+        encoder: &'static (dyn StateEncoder + Sync),
+        decoder: &'static (dyn ActionDecoder + Sync),
+    ) -> String {
+        format!("{}/{}", encoder.name(), decoder.name())
+    }
+
     pub fn config() -> DomainConfig {
+        Self::config_with(&RichEncoder, &MaskedDecoder)
+    }
+
+    pub fn config_with(
+        encoder: &'static (dyn StateEncoder + Sync),
+        decoder: &'static (dyn ActionDecoder + Sync),
+    ) -> DomainConfig {
+        log::info!(
+            "Encoding state with the {} encoder, decoding output with the {} decoder",
+            encoder.name(),
+            decoder.name()
+        );
         DomainConfig {
-            input_layer_size: Self::INPUT_LAYER_SIZE,
-            output_layer_size: Self::OUTPUT_LAYER_SIZE,
-            fitness: Box::new(|n| {
-                let actual = NeatAgent::new(n).fitness();
-                sigmoid(actual)
-            }),
+            input_layer_size: encoder.size() + decoder.extra_inputs(),
+            output_layer_size: decoder.size(),
+            encoding: Self::encoding(encoder, decoder),
+            fitness: Box::new(move |n, seeds| NeatAgent::new(n, encoder, decoder).fitness(seeds)),
         }
     }
 }
+
+// every encoder, so saved genomes can name theirs
+pub static ENCODERS: [&(dyn StateEncoder + Sync); 2] = [&BasicEncoder, &RichEncoder];
+
+// Turns a game state into the network's input.
+pub trait StateEncoder {
+    fn name(&self) -> &'static str;
+    // length of the encoded input
+    fn size(&self) -> usize;
+    fn encode(&self, state: &GameState) -> Vec<f64>;
+}
+
+// The pattern drawn on each tile, and the dice.
+pub struct BasicEncoder;
+
+impl StateEncoder for BasicEncoder {
+    fn name(&self) -> &'static str {
+        "basic"
+    }
+
+    // 34 patterns
+    // 49 tiles
+    // 3 regular dice with 6 unique sides
+    // 1 special die with 3 unique sides
+    #[allow(clippy::identity_op)]
+    fn size(&self) -> usize {
+        34 * 49 // drawn on grid
+        +  3 *  6 // regular dice
+        +  1 *  3 // special die
+    }
+
+    fn encode(&self, state: &GameState) -> Vec<f64> {
+        let mut input = Vec::with_capacity(self.size());
+        encode_routes(state, &mut input);
+        encode_dice(state, &mut input);
+        input
+    }
+}
+
+// Everything in the basic encoding, plus what's needed to plan ahead:
+// open edges, blocked tiles, the round, special routes used so far, and connected exits.
+pub struct RichEncoder;
+
+impl StateEncoder for RichEncoder {
+    fn name(&self) -> &'static str {
+        "rich"
+    }
+
+    fn size(&self) -> usize {
+        BasicEncoder.size()
+        + 49 * 4 * 2 // open edges, by direction and piece
+        + 49         // blocked tiles
+        + GameState::ROUNDS
+        + 3          // special routes used
+        + EXITS.len()
+    }
+
+    fn encode(&self, state: &GameState) -> Vec<f64> {
+        let mut input = Vec::with_capacity(self.size());
+        encode_routes(state, &mut input);
+        encode_dice(state, &mut input);
+
+        let offset = input.len();
+        input.resize(offset + 49 * 4 * 2, 0.0);
+        for (edge, piece) in state.open_edges.iter() {
+            let tile = (edge.tile.x + edge.tile.y * 7) as usize;
+            input[offset + tile * 8 + edge.direction as usize * 2 + *piece as usize] = 1.0;
+        }
+
+        let offset = input.len();
+        input.resize(offset + 49, 0.0);
+        for tile in state.blocked_tiles.keys() {
+            input[offset + (tile.x + tile.y * 7) as usize] = 1.0;
+        }
+
+        input.extend((0..GameState::ROUNDS).map(|round| (round == state.round) as u8 as f64));
+
+        let specials = [
+            DieFace::Overpass,
+            DieFace::StraightStation,
+            DieFace::AngleStation,
+        ];
+        input.extend(specials.iter().map(|face| {
+            let used = state
+                .drawn_routes
+                .iter()
+                .any(|action| action.pattern.face == *face);
+            used as u8 as f64
+        }));
+
+        input.extend(EXITS.iter().map(|(edge, piece)| {
+            let connected = state
+                .route_at(edge.tile)
+                .is_some_and(|route| route.get(edge.direction) == Some(*piece));
+            connected as u8 as f64
+        }));
+        input
+    }
+}
+
+// one hot pattern for each tile
+fn encode_routes(state: &GameState, input: &mut Vec<f64>) {
+    let offset = input.len();
+    input.resize(offset + 34 * 49, 0.0);
+    for action in state.drawn_routes.iter() {
+        let tile_offset = 34 * (action.tile.x + action.tile.y * 7) as usize;
+        input[offset + tile_offset + action.pattern.index] = 1.0;
+    }
+}
+
+// one hot face for each die
+fn encode_dice(state: &GameState, input: &mut Vec<f64>) {
+    for face in state.dice.regular {
+        let offset = input.len();
+        input.resize(offset + 6, 0.0);
+        input[offset + face as usize] = 1.0;
+    }
+    let offset = input.len();
+    input.resize(offset + 3, 0.0);
+    input[offset + (state.dice.special as usize - 6)] = 1.0;
+}
+
+// every decoder, so saved genomes can name theirs
+pub static DECODERS: [&(dyn ActionDecoder + Sync); 3] =
+    [&ArgmaxDecoder, &MaskedDecoder, &CompactDecoder];
+
+// Runs the network on the encoded state, and turns its output into the routes to draw.
+pub trait ActionDecoder {
+    fn name(&self) -> &'static str;
+    // number of outputs
+    fn size(&self) -> usize;
+    // inputs after the encoded state, for decoders that query the network more than once
+    fn extra_inputs(&self) -> usize {
+        0
+    }
+    fn decode(
+        &self,
+        network: &mut dyn NeuralInterface,
+        encoder: &dyn StateEncoder,
+        state: &GameState,
+    ) -> Turn;
+}
+
+// The original decoding: the best placement in each die's window, if it's over 0.5.
+// It doesn't look at the dice or the board, so most of its placements are errors.
+pub struct ArgmaxDecoder;
+
+impl ActionDecoder for ArgmaxDecoder {
+    fn name(&self) -> &'static str {
+        "argmax"
+    }
+
+    fn size(&self) -> usize {
+        NeatAgentMethods::OUTPUT_LAYER_SIZE
+    }
+
+    fn decode(
+        &self,
+        network: &mut dyn NeuralInterface,
+        encoder: &dyn StateEncoder,
+        state: &GameState,
+    ) -> Turn {
+        NeatAgentMethods::from_output(network.run(&encoder.encode(state)))
+    }
+}
+
+// Only looks at legal placements of the dice that were rolled.
+// The best one is drawn, then the rest are masked again against the new board,
+// until none of the remaining dice fit.
+pub struct MaskedDecoder;
+
+impl ActionDecoder for MaskedDecoder {
+    fn name(&self) -> &'static str {
+        "masked"
+    }
+
+    fn size(&self) -> usize {
+        NeatAgentMethods::OUTPUT_LAYER_SIZE
+    }
+
+    fn decode(
+        &self,
+        network: &mut dyn NeuralInterface,
+        encoder: &dyn StateEncoder,
+        state: &GameState,
+    ) -> Turn {
+        let output = network.run(&encoder.encode(state));
+        let action_window = 49 * 34;
+        let mut state = state.clone();
+        // each die has its own window, in the order of Dice::faces
+        let mut dice: Vec<(usize, DieFace)> = state.dice.faces().into_iter().enumerate().collect();
+        let mut actions = Vec::with_capacity(4);
+        loop {
+            let best = best_legal_action(&state, &dice, |window, action| {
+                let tile = (action.tile.x + action.tile.y * 7) as usize;
+                output[window * action_window + tile * 34 + action.pattern.index]
+            });
+            let Some((i, action)) = best else {
+                return Turn { actions };
+            };
+            state.apply_route(action).expect("legal action");
+            actions.push(action);
+            dice.remove(i);
+        }
+    }
+}
+
+// One output per tile and one per orientation of a face, instead of one per placement.
+// The network is queried for each die, with the die's face as an extra one hot input,
+// and a placement scores the sum of its tile and orientation outputs.
+pub struct CompactDecoder;
+
+impl CompactDecoder {
+    // the angled station has the most
+    const ORIENTATIONS: usize = 8;
+}
+
+impl ActionDecoder for CompactDecoder {
+    fn name(&self) -> &'static str {
+        "compact"
+    }
+
+    fn size(&self) -> usize {
+        49 + Self::ORIENTATIONS
+    }
+
+    fn extra_inputs(&self) -> usize {
+        DieFace::ALL.len()
+    }
+
+    fn decode(
+        &self,
+        network: &mut dyn NeuralInterface,
+        encoder: &dyn StateEncoder,
+        state: &GameState,
+    ) -> Turn {
+        let mut state = state.clone();
+        let mut remaining = state.dice.faces().to_vec();
+        let mut actions = Vec::with_capacity(4);
+        loop {
+            // the board changes after every route, so each die is asked about it again
+            let mut outputs: Vec<(DieFace, Vec<f64>)> = Vec::with_capacity(remaining.len());
+            let encoded = encoder.encode(&state);
+            for face in remaining.iter() {
+                if outputs.iter().any(|(f, _)| f == face) {
+                    continue;
+                }
+                let mut input = encoded.clone();
+                input.extend(DieFace::ALL.iter().map(|f| (f == face) as u8 as f64));
+                outputs.push((*face, network.run(&input).to_vec()));
+            }
+
+            let dice: Vec<(DieFace, DieFace)> =
+                remaining.iter().map(|face| (*face, *face)).collect();
+            let best = best_legal_action(&state, &dice, |face, action| {
+                let (_, output) = outputs.iter().find(|(f, _)| *f == face).expect("queried");
+                let tile = (action.tile.x + action.tile.y * 7) as usize;
+                let orientation = action.pattern.index - face.routes()[0].index;
+                output[tile] + output[49 + orientation]
+            });
+            let Some((i, action)) = best else {
+                return Turn { actions };
+            };
+            state.apply_route(action).expect("legal action");
+            actions.push(action);
+            remaining.remove(i);
+        }
+    }
+}
+
+// The best scoring legal route of any of the dice, and which die it's for.
+// Dice are keyed by whatever the score needs to tell them apart,
+// a die with the same key and face as an earlier one isn't scored again.
+pub(crate) fn best_legal_action<K: Copy + PartialEq, V: PartialOrd>(
+    state: &GameState,
+    dice: &[(K, DieFace)],
+    mut score: impl FnMut(K, &DrawAction) -> V,
+) -> Option<(usize, DrawAction)> {
+    let mut best: Option<(V, usize, DrawAction)> = None;
+    for (i, die) in dice.iter().enumerate() {
+        if dice[..i].contains(die) {
+            continue;
+        }
+        let (key, face) = *die;
+        for action in state.legal_actions(face) {
+            let value = score(key, &action);
+            if best.as_ref().is_none_or(|(best, _, _)| value > *best) {
+                best = Some((value, i, action));
+            }
+        }
+    }
+    best.map(|(_, i, action)| (i, action))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::puzzle::Puzzle;
+    use crate::rri::{Dice, RegularDieFace, SpecialDieFace};
+
+    // same output for any input
+    struct Fixed {
+        inputs: usize,
+        output: Vec<f64>,
+    }
+
+    impl NeuralInterface for Fixed {
+        fn run(&mut self, input: &[f64]) -> &[f64] {
+            assert_eq!(input.len(), self.inputs);
+            &self.output
+        }
+    }
+
+    // plays a turn that was decoded up front
+    struct Decoded(Turn);
+
+    impl RRIAgent for Decoded {
+        fn prompt(&mut self, _state: &GameState) -> Turn {
+            Turn {
+                actions: std::mem::take(&mut self.0.actions),
+            }
+        }
+
+        fn handle_error(&mut self, error: anyhow::Error) {
+            panic!("{}", error);
+        }
+    }
+
+    #[test]
+    fn test_encoders_fill_their_layer() {
+        let mut state = GameState::new();
+        state
+            .apply_route(DrawAction {
+                pattern: &DIE_PATTERNS[8],
+                tile: Tile { x: 3, y: 0 },
+            })
+            .unwrap();
+        for encoder in [&BasicEncoder as &dyn StateEncoder, &RichEncoder] {
+            assert_eq!(encoder.encode(&state).len(), encoder.size());
+        }
+
+        // indexed by pattern, so rotations of the same face are told apart
+        let input = BasicEncoder.encode(&state);
+        assert_eq!(input[3 * 34 + 8], 1.0);
+        assert_eq!(input[3 * 34 + DieFace::StraightRail as usize], 0.0);
+    }
+
+    #[test]
+    fn test_masked_decoder_only_draws_legal_routes() {
+        let mut output = vec![0.0; MaskedDecoder.size()];
+        // the argmax is a rolled die, but in the middle of the empty board
+        output[24 * 34 + 8] = 1.0;
+        let mut network = Fixed {
+            inputs: BasicEncoder.size(),
+            output,
+        };
+        let mut state = GameState::new();
+        state.dice = Dice {
+            regular: [RegularDieFace::StraightRail; 3],
+            special: SpecialDieFace::Overpass,
+        };
+
+        let argmax = ArgmaxDecoder.decode(&mut network, &BasicEncoder, &state);
+        assert_eq!(argmax.actions[0].tile, Tile::CENTER);
+
+        let turn = MaskedDecoder.decode(&mut network, &BasicEncoder, &state);
+        let result = state.play_turn(&mut Decoded(turn));
+        assert_eq!(result.placed.len(), 4);
+        assert_eq!(result.errors, 0);
+    }
+
+    #[test]
+    fn test_argmax_tiles_match_the_encoders() {
+        let tile = Tile { x: 1, y: 3 };
+        let mut output = vec![0.0; NeatAgentMethods::OUTPUT_LAYER_SIZE];
+        output[(tile.x + tile.y * 7) as usize * 34 + 8] = 1.0;
+        let turn = NeatAgentMethods::from_output(&output);
+        assert_eq!(turn.actions[0].tile, tile);
+    }
+
+    #[test]
+    fn test_compact_decoder_queries_each_die() {
+        let mut network = Fixed {
+            inputs: RichEncoder.size() + CompactDecoder.extra_inputs(),
+            output: (0..CompactDecoder.size()).map(|i| i as f64).collect(),
+        };
+        let mut state = GameState::new();
+        state.dice = Dice {
+            regular: [
+                RegularDieFace::AngleRoad,
+                RegularDieFace::ThreeRail,
+                RegularDieFace::StraightRoad,
+            ],
+            special: SpecialDieFace::AngleStation,
+        };
+
+        let turn = CompactDecoder.decode(&mut network, &RichEncoder, &state);
+        let result = state.play_turn(&mut Decoded(turn));
+        assert_eq!(result.placed.len(), 4);
+        assert_eq!(result.errors, 0);
+    }
+
+    #[test]
+    fn test_saved_genome_plays_with_its_encoding() {
+        use crate::neat::genome::{Gene, GeneId, NodeId};
+        use std::sync::Arc;
+
+        let encoding = NeatAgentMethods::encoding(&BasicEncoder, &CompactDecoder);
+        let in_nodes = BasicEncoder.size() + CompactDecoder.extra_inputs();
+        let genome = Genome {
+            genes: vec![Arc::new(Gene {
+                id: GeneId(0),
+                in_node: NodeId(0),
+                out_node: NodeId(in_nodes),
+                weight: 0.5,
+                enabled: true,
+            })],
+            hidden_nodes: vec![],
+            in_nodes,
+            out_nodes: CompactDecoder.size(),
+        };
+        let path = std::env::temp_dir().join(format!("genome-{}.json", std::process::id()));
+
+        genome.save(&path, &encoding).unwrap();
+        let mut agent = NeatAgent::load(&path).unwrap();
+        assert!(agent.fitness(&[1]) > 0.0);
+
+        // the same genome can't be read by another decoder
+        genome.save(&path, "basic/masked").unwrap();
+        assert!(NeatAgent::load(&path).is_err());
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_random_agent_only_passes_when_stuck() {
+        let mut game = GameState::new();
+        let mut agent = RandomAgent::seeded(7);
+        for result in game.play(&mut agent) {
+            assert_eq!(result.errors, 0);
+            assert!(result.skipped.is_empty());
+        }
+        assert!(game.validate().is_ok());
+    }
+
+    #[test]
+    fn test_greedy_turn_is_at_least_as_good_as_random_turn() {
+        let mut state = Puzzle::new()
+            .roll(Dice {
+                regular: [
+                    RegularDieFace::StraightRail,
+                    RegularDieFace::AngleRoad,
+                    RegularDieFace::StraightRail,
+                ],
+                special: SpecialDieFace::Overpass,
+            })
+            .build()
+            .unwrap();
+        let mut greedy = state.clone();
+        greedy.play_turn(&mut GreedyAgent::default());
+        state.play_turn(&mut RandomAgent::seeded(7));
+        assert!(greedy.score() >= state.score());
+    }
+}
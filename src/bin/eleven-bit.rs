@@ -1,15 +1,17 @@
 use railroad_inc::{
     logger,
-    neat::genome::{Config, DomainConfig, Parameters, Population},
+    neat::genome::{Checkpointing, Config, DomainConfig, Parameters, Population},
     neat::network::{Network, NeuralInterface},
 };
-use rand::Rng;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
 
-fn fitness(network: &mut Network) -> f64 {
+// one test case per seed
+fn fitness(network: &mut Network, seeds: &[u64]) -> f64 {
     let mut score = 1.0_f64;
     let mut input = [0.0; 11];
-    for _ in 0..100 {
-        let mut test_case: usize = rand::thread_rng().gen_range(0..2048);
+    for seed in seeds {
+        let mut test_case: usize = StdRng::seed_from_u64(*seed).gen_range(0..2048);
         let expected_address = test_case & 7;
         let expected_output = (test_case >> (expected_address + 3)) & 1;
         for bit in &mut input {
@@ -26,19 +28,29 @@ fn fitness(network: &mut Network) -> f64 {
 
 fn main() {
     logger::init();
-    let mut parameters = Parameters::default();
-    parameters.population = 10;
     let config = Config {
-        parameters,
+        parameters: Parameters {
+            population: 10,
+            checkpointing: Some(Checkpointing {
+                every: 100,
+                path: "checkpoints/eleven-bit.json".into(),
+            }),
+            ..Default::default()
+        },
         domain: DomainConfig {
             input_layer_size: 11,
             output_layer_size: 1,
+            encoding: "eleven-bit".to_string(),
             fitness: Box::new(fitness),
         },
     };
-    let mut population = Population::new(config);
-    for gen in 0..10_000 {
-        population.advance_gen();
+    std::fs::create_dir_all("checkpoints").unwrap();
+    std::fs::create_dir_all("reports").unwrap();
+    let mut population = Population::resume(config).unwrap();
+    while population.generation < 10_000 {
+        let gen = population.generation;
+        let report = population.advance_gen();
+        report.write_csv("reports/eleven-bit.csv").unwrap();
 
         if population.champion.fitness.actual > 95.0 {
             log::info!(
@@ -1,20 +1,66 @@
 use railroad_inc::{
-    agent::NeatAgentMethods,
+    agent::{ArgmaxDecoder, CompactDecoder, MaskedDecoder, NeatAgentMethods, RichEncoder},
+    features::FeatureAgent,
     logger,
-    neat::genome::{Config, Parameters, Population},
+    neat::genome::{Checkpointing, Config, Parameters, Population, ThresholdControl},
 };
 
+const USAGE: &str = "usage: rri [argmax|masked|compact|features]";
+
 fn main() {
     logger::init();
+    let mode = std::env::args().nth(1).unwrap_or("masked".to_string());
+    let domain = match mode.as_str() {
+        "argmax" => NeatAgentMethods::config_with(&RichEncoder, &ArgmaxDecoder),
+        "masked" => NeatAgentMethods::config_with(&RichEncoder, &MaskedDecoder),
+        "compact" => NeatAgentMethods::config_with(&RichEncoder, &CompactDecoder),
+        "features" => FeatureAgent::config(),
+        _ => {
+            eprintln!("unknown mode {mode}\n{USAGE}");
+            std::process::exit(2);
+        }
+    };
     let mut config = Config {
-        domain: NeatAgentMethods::config(),
+        domain,
         parameters: Parameters::default(),
     };
     config.parameters.population = 100;
-    let mut population = Population::new(config);
-    log::info!("Gen 0");
-    for gen in 1..10 {
-        population.advance_gen();
-        log::info!("Gen {}", gen);
+    config.parameters.speciation.control = Some(ThresholdControl {
+        target: 10,
+        step: 0.3,
+        min: 0.5,
+        max: 20.0,
+    });
+    if mode == "features" {
+        // every placement is evaluated, so games are slower
+        config.parameters.games = 20;
+    }
+    std::fs::create_dir_all("checkpoints").unwrap();
+    config.parameters.checkpointing = Some(Checkpointing {
+        every: 1,
+        path: format!("checkpoints/rri-{mode}.json").into(),
+    });
+    let mut population = Population::resume(config).unwrap();
+    log::info!("Gen {}", population.generation);
+    std::fs::create_dir_all("champions").unwrap();
+    std::fs::create_dir_all("reports").unwrap();
+    while population.generation < 10 {
+        let report = population.advance_gen();
+        log::info!(
+            "Gen {}: actual max {:.3}, mean {:.3}, {} species at {:.2}, {:.1}s",
+            report.generation,
+            report.actual.max,
+            report.actual.mean,
+            report.species.len(),
+            report.threshold,
+            report.timings.total
+        );
+        report.write_csv(format!("reports/rri-{mode}.csv")).unwrap();
+        report
+            .write_jsonl(format!("reports/rri-{mode}.jsonl"))
+            .unwrap();
+        population
+            .save_champion(format!("champions/rri-{mode}.json"))
+            .unwrap();
     }
 }
@@ -0,0 +1,78 @@
+use railroad_inc::{
+    agent::{GreedyAgent, NeatAgent, RandomAgent},
+    features::FeatureAgent,
+    logger,
+    mcts::{MctsAgent, MctsConfig},
+    neat::genome::Genome,
+    rri::RRIAgent,
+    tournament::Tournament,
+};
+
+const USAGE: &str = "usage: tournament [games] [seed] [--greedy] [--mcts] [genome files...]";
+
+// Greedy takes tens of seconds a game and MCTS minutes, so they only play when asked.
+fn main() {
+    logger::init();
+    let mut args = std::env::args().skip(1);
+    let games = args.next().map_or(20, |arg| arg.parse().expect("games"));
+    let seed = args.next().map_or(0, |arg| arg.parse().expect("seed"));
+    let (flags, paths): (Vec<String>, Vec<String>) = args.partition(|arg| arg.starts_with("--"));
+    for flag in flags.iter() {
+        if flag != "--greedy" && flag != "--mcts" {
+            eprintln!("unknown flag {flag}\n{USAGE}");
+            std::process::exit(2);
+        }
+    }
+
+    let mut random = RandomAgent::seeded(seed);
+    let mut greedy = GreedyAgent::default();
+    let mut mcts = MctsAgent::seeded(MctsConfig::default(), seed);
+    // trained bots, named after their files
+    let mut bots: Vec<(String, Box<dyn RRIAgent>)> = paths
+        .into_iter()
+        .map(|path| {
+            let (_, encoding) = Genome::load(&path).expect("genome");
+            let bot: Box<dyn RRIAgent> = if encoding == FeatureAgent::ENCODING {
+                Box::new(FeatureAgent::load(&path).expect("genome"))
+            } else {
+                Box::new(NeatAgent::load(&path).expect("genome"))
+            };
+            (path, bot)
+        })
+        .collect();
+
+    let mut entrants: Vec<(&str, &mut dyn RRIAgent)> = vec![("random", &mut random)];
+    if flags.iter().any(|flag| flag == "--greedy") {
+        entrants.push(("greedy", &mut greedy));
+    }
+    if flags.iter().any(|flag| flag == "--mcts") {
+        entrants.push(("mcts", &mut mcts));
+    }
+    for (name, bot) in bots.iter_mut() {
+        entrants.push((name, bot.as_mut()));
+    }
+    let report = Tournament::new(games, seed).run(&mut entrants);
+
+    for agent in report.agents.iter() {
+        log::info!(
+            "{}: mean {:.1}, median {:.1}, std {:.1}, errors/round {:.3}",
+            agent.name,
+            agent.mean,
+            agent.median,
+            agent.std_dev,
+            agent.error_rate
+        );
+    }
+    for pair in report.pairs.iter() {
+        log::info!(
+            "{} vs {}: {}-{}-{}",
+            pair.agent,
+            pair.opponent,
+            pair.wins,
+            pair.losses,
+            pair.draws
+        );
+    }
+    report.write_json("tournament.json").unwrap();
+    report.write_csv("tournament.csv").unwrap();
+}
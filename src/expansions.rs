@@ -0,0 +1,389 @@
+use crate::rri::{Direction, GameState, Tile, TileEdge};
+use rand::seq::SliceRandom;
+use rand::Rng;
+
+// The Blazing Red expansions.
+// Each one rolls an event die at the start of every round,
+// and the event is resolved on the board before the route dice are drawn.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Variant {
+    #[default]
+    Base,
+    Lava,
+    Meteor,
+}
+
+impl Variant {
+    pub fn roll_event<R: Rng + ?Sized>(&self, rng: &mut R) -> Option<EventRoll> {
+        match self {
+            Variant::Base => None,
+            Variant::Lava => Some(EventRoll::Lava(
+                LavaDieFace::ALL[rng.gen_range(0..LavaDieFace::ALL.len())],
+            )),
+            Variant::Meteor => Some(EventRoll::Meteor(MeteorRoll {
+                direction: Direction::ALL[rng.gen_range(0..4)],
+                distance: rng.gen_range(1..=6),
+            })),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum EventRoll {
+    Lava(LavaDieFace),
+    Meteor(MeteorRoll),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LavaDieFace {
+    Volcano,
+    Single,
+    Angle,
+    Straight,
+    Three,
+    Cross,
+}
+
+impl LavaDieFace {
+    pub const ALL: [LavaDieFace; 6] = [
+        LavaDieFace::Volcano,
+        LavaDieFace::Single,
+        LavaDieFace::Angle,
+        LavaDieFace::Straight,
+        LavaDieFace::Three,
+        LavaDieFace::Cross,
+    ];
+
+    // Every distinct rotation of the lava drawn on this face.
+    pub fn patterns(&self) -> Vec<LavaPattern> {
+        // north, east, south, west
+        let edges = match self {
+            LavaDieFace::Volcano | LavaDieFace::Cross => [true, true, true, true],
+            LavaDieFace::Single => [true, false, false, false],
+            LavaDieFace::Angle => [true, true, false, false],
+            LavaDieFace::Straight => [true, false, true, false],
+            LavaDieFace::Three => [true, true, true, false],
+        };
+        let mut pattern = LavaPattern { edges };
+        let mut patterns = Vec::with_capacity(4);
+        for _ in 0..4 {
+            if !patterns.contains(&pattern) {
+                patterns.push(pattern);
+            }
+            pattern = pattern.rotate();
+        }
+        patterns
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct LavaPattern {
+    edges: [bool; 4],
+}
+
+impl LavaPattern {
+    pub const VOLCANO: LavaPattern = LavaPattern { edges: [true; 4] };
+
+    pub fn has_lava(&self, direction: Direction) -> bool {
+        self.edges[direction as usize]
+    }
+
+    // quarter turn clockwise
+    fn rotate(self) -> Self {
+        let mut edges = [false; 4];
+        for direction in Direction::ALL {
+            edges[direction.rotate() as usize] = self.edges[direction as usize];
+        }
+        Self { edges }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Blocker {
+    Volcano,
+    Lava(LavaPattern),
+    Crater,
+}
+
+impl Blocker {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Blocker::Volcano => "volcano",
+            Blocker::Lava(_) => "lava",
+            Blocker::Crater => "crater",
+        }
+    }
+
+    pub fn lava(&self) -> Option<LavaPattern> {
+        match self {
+            Blocker::Volcano => Some(LavaPattern::VOLCANO),
+            Blocker::Lava(pattern) => Some(*pattern),
+            Blocker::Crater => None,
+        }
+    }
+}
+
+// The meteor dice: a direction and a distance rolled on a d6.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct MeteorRoll {
+    pub direction: Direction,
+    pub distance: u8,
+}
+
+impl MeteorRoll {
+    // The meteor flies out from the center of the board,
+    // and bounces back when it would overshoot the board's edge.
+    pub fn landing(&self) -> Tile {
+        let bounce = |from: u8, forward: bool| {
+            let to = if forward {
+                from as i8 + self.distance as i8
+            } else {
+                from as i8 - self.distance as i8
+            };
+            let to = if to > 6 { 12 - to } else { to.abs() };
+            to as u8
+        };
+        let Tile { x, y } = Tile::CENTER;
+        match self.direction {
+            Direction::North => Tile {
+                x,
+                y: bounce(y, false),
+            },
+            Direction::South => Tile {
+                x,
+                y: bounce(y, true),
+            },
+            Direction::West => Tile {
+                x: bounce(x, false),
+                y,
+            },
+            Direction::East => Tile {
+                x: bounce(x, true),
+                y,
+            },
+        }
+    }
+}
+
+impl GameState {
+    // Where lava flows and volcanoes erupt is chosen by the rng.
+    pub fn resolve_event<R: Rng + ?Sized>(&mut self, event: EventRoll, rng: &mut R) {
+        match event {
+            EventRoll::Lava(face) => self.spread_lava(face, rng),
+            EventRoll::Meteor(roll) => self.strike_meteor(roll),
+        }
+    }
+
+    // Lava has to flow out of an open lava edge.
+    // When there is nowhere for it to flow, a new volcano erupts instead.
+    fn spread_lava<R: Rng + ?Sized>(&mut self, face: LavaDieFace, rng: &mut R) {
+        if face == LavaDieFace::Volcano {
+            return self.erupt(rng);
+        }
+        let mut candidates = Vec::new();
+        for edge in self.open_lava_edges() {
+            for pattern in face.patterns() {
+                // the source of the lava is on the far side of the open edge
+                if pattern.has_lava(edge.direction) {
+                    candidates.push((edge.tile, pattern));
+                }
+            }
+        }
+        match candidates.choose(rng) {
+            Some((tile, pattern)) => self.block_tile(*tile, Blocker::Lava(*pattern)),
+            None => self.erupt(rng),
+        }
+    }
+
+    fn erupt<R: Rng + ?Sized>(&mut self, rng: &mut R) {
+        let empty: Vec<Tile> = Tile::all().filter(|tile| self.is_empty(*tile)).collect();
+        if let Some(tile) = empty.choose(rng) {
+            self.block_tile(*tile, Blocker::Volcano);
+        }
+    }
+
+    // The impact erases whatever route was drawn there, and leaves a crater behind.
+    fn strike_meteor(&mut self, roll: MeteorRoll) {
+        let tile = roll.landing();
+        self.remove_route(tile);
+        self.block_tile(tile, Blocker::Crater);
+    }
+
+    // Edges of empty tiles that lava is flowing into.
+    pub fn open_lava_edges(&self) -> Vec<TileEdge> {
+        let mut edges = Vec::new();
+        for (tile, blocker) in self.blocked_tiles.iter() {
+            let Some(lava) = blocker.lava() else {
+                continue;
+            };
+            for direction in Direction::ALL {
+                if !lava.has_lava(direction) {
+                    continue;
+                }
+                let edge = TileEdge {
+                    tile: *tile,
+                    direction,
+                };
+                if let Some(adjacent) = edge.adjacent() {
+                    if self.is_empty(adjacent.tile) {
+                        edges.push(adjacent);
+                    }
+                }
+            }
+        }
+        // in board order, so a seeded rng picks the same edge every time
+        edges.sort_unstable_by_key(|edge| (edge.tile.y, edge.tile.x, edge.direction as u8));
+        edges
+    }
+
+    // Connected groups of lava tiles.
+    pub fn lava_lakes(&self) -> Vec<Vec<Tile>> {
+        let mut lakes: Vec<Vec<Tile>> = Vec::new();
+        let mut visited: Vec<Tile> = Vec::new();
+        for tile in self.blocked_tiles.keys() {
+            if visited.contains(tile) || self.lava_at(*tile).is_none() {
+                continue;
+            }
+            let mut lake = vec![];
+            let mut stack = vec![*tile];
+            visited.push(*tile);
+            while let Some(tile) = stack.pop() {
+                lake.push(tile);
+                let lava = self.lava_at(tile).expect("only lava is pushed");
+                for direction in Direction::ALL {
+                    let Some(neighbor) = tile.neighbor(direction) else {
+                        continue;
+                    };
+                    if visited.contains(&neighbor) {
+                        continue;
+                    }
+                    let Some(other) = self.lava_at(neighbor) else {
+                        continue;
+                    };
+                    if lava.has_lava(direction) || other.has_lava(direction.opposite()) {
+                        visited.push(neighbor);
+                        stack.push(neighbor);
+                    }
+                }
+            }
+            lakes.push(lake);
+        }
+        lakes
+    }
+
+    // A lake is contained once none of its lava can flow anywhere.
+    pub fn contained_lava_lakes(&self) -> usize {
+        let open = self.open_lava_edges();
+        self.lava_lakes()
+            .iter()
+            .filter(|lake| {
+                !open.iter().any(|edge| {
+                    edge.adjacent()
+                        .is_some_and(|source| lake.contains(&source.tile))
+                })
+            })
+            .count()
+    }
+
+    // Craters with at least one route leading into them.
+    pub fn mined_craters(&self) -> usize {
+        self.blocked_tiles
+            .iter()
+            .filter(|(_, blocker)| **blocker == Blocker::Crater)
+            .filter(|(tile, _)| {
+                Direction::ALL.iter().any(|direction| {
+                    tile.neighbor(*direction).is_some_and(|neighbor| {
+                        self.route_at(neighbor)
+                            .is_some_and(|route| route.get(direction.opposite()).is_some())
+                    })
+                })
+            })
+            .count()
+    }
+
+    pub fn lava_at(&self, tile: Tile) -> Option<LavaPattern> {
+        self.blocked_tiles.get(&tile).and_then(Blocker::lava)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::agent::RandomAgent;
+    use crate::routes::DIE_PATTERNS;
+    use crate::rri::DrawAction;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    #[test]
+    fn test_meteor_bounces_off_board_edge() {
+        let roll = |direction, distance| MeteorRoll {
+            direction,
+            distance,
+        };
+        assert_eq!(roll(Direction::North, 2).landing(), Tile { x: 3, y: 1 });
+        assert_eq!(roll(Direction::East, 4).landing(), Tile { x: 5, y: 3 });
+        assert_eq!(roll(Direction::West, 6).landing(), Tile::CENTER);
+    }
+
+    #[test]
+    fn test_seeded_lava_games_replay() {
+        let play = || {
+            let mut state = GameState::with_variant(Variant::Lava);
+            state.play_with(&mut RandomAgent::seeded(2), &mut StdRng::seed_from_u64(7));
+            let mut blocked: Vec<(u8, u8)> =
+                state.blocked_tiles.keys().map(|t| (t.x, t.y)).collect();
+            blocked.sort_unstable();
+            (blocked, state.score())
+        };
+        assert_eq!(play(), play());
+    }
+
+    #[test]
+    fn test_meteor_destroys_route() {
+        let mut state = GameState::with_variant(Variant::Meteor);
+        // straight rail, from the north exit to the center
+        for y in 0..3 {
+            state
+                .apply_route(DrawAction {
+                    pattern: &DIE_PATTERNS[8],
+                    tile: Tile { x: 3, y },
+                })
+                .unwrap();
+        }
+        let roll = MeteorRoll {
+            direction: Direction::North,
+            distance: 1,
+        };
+        state.resolve_event(EventRoll::Meteor(roll), &mut rand::thread_rng());
+
+        let crater = Tile { x: 3, y: 2 };
+        assert!(state.route_at(crater).is_none());
+        assert_eq!(state.blocked_tiles.get(&crater), Some(&Blocker::Crater));
+        assert_eq!(state.mined_craters(), 1);
+        assert!(state
+            .apply_route(DrawAction {
+                pattern: &DIE_PATTERNS[8],
+                tile: crater,
+            })
+            .is_err());
+    }
+
+    #[test]
+    fn test_contained_lava_lake() {
+        let mut state = GameState::with_variant(Variant::Lava);
+        state.block_tile(Tile { x: 0, y: 0 }, Blocker::Volcano);
+        assert_eq!(state.contained_lava_lakes(), 0);
+
+        state.block_tile(
+            Tile { x: 1, y: 0 },
+            Blocker::Lava(LavaDieFace::Single.patterns()[3]),
+        );
+        state.block_tile(
+            Tile { x: 0, y: 1 },
+            Blocker::Lava(LavaDieFace::Single.patterns()[0]),
+        );
+        assert_eq!(state.lava_lakes().len(), 1);
+        assert_eq!(state.contained_lava_lakes(), 1);
+    }
+}
@@ -0,0 +1,209 @@
+use crate::agent::{best_legal_action, every_turn, route_key};
+use crate::expansions::Blocker;
+use crate::rri::{Dice, DieFace, DrawAction, GameState, RRIAgent, Tile, Turn};
+use std::collections::HashMap;
+
+// Value of a board at the end of the lookahead.
+pub type Evaluation = Box<dyn Fn(&GameState) -> f64>;
+
+// Approximate expectimax: looks ahead a fixed number of rounds, averaging over every possible roll.
+// Only the best few of this round's turns are looked ahead from, and lookahead rounds draw one die
+// at a time, so a turn that only pays off in another order can be missed.
+// Expansion events aren't modelled, the lookahead assumes none happen.
+pub struct ApproxExpectimaxAgent {
+    // rolls to look ahead after this round's dice, the search is exponential in this.
+    pub depth: usize,
+    // only this many of this round's turns, the best by evaluation, are looked ahead from.
+    pub candidates: usize,
+    eval: Evaluation,
+    outcomes: Vec<(Dice, f64)>,
+}
+
+impl Default for ApproxExpectimaxAgent {
+    fn default() -> Self {
+        Self::new(1, 4, Box::new(|state| state.score() as f64))
+    }
+}
+
+impl ApproxExpectimaxAgent {
+    pub fn new(depth: usize, candidates: usize, eval: Evaluation) -> Self {
+        Self {
+            depth,
+            candidates,
+            eval,
+            outcomes: Dice::outcomes(),
+        }
+    }
+
+    // Best turn for the given dice, in this state.
+    pub fn best_turn(&self, state: &GameState, dice: &[DieFace]) -> Turn {
+        let mut search = Search {
+            agent: self,
+            evals: HashMap::new(),
+        };
+        let mut turns = Vec::new();
+        every_turn(state, dice, |actions, board| {
+            turns.push((actions.to_vec(), board.clone()))
+        });
+
+        let mut ranked: Vec<(f64, Vec<DrawAction>, GameState)> = turns
+            .into_iter()
+            .map(|(actions, board)| (search.after_turn(&board, 0), actions, board))
+            .collect();
+        ranked.sort_by(|a, b| b.0.total_cmp(&a.0));
+        if self.depth > 0 {
+            ranked.truncate(self.candidates.max(1));
+            for (value, _, board) in ranked.iter_mut() {
+                *value = search.after_turn(board, self.depth);
+            }
+        }
+        let best = ranked
+            .into_iter()
+            .reduce(|best, turn| if turn.0 > best.0 { turn } else { best });
+        Turn {
+            actions: best.map_or(vec![], |(_, actions, _)| actions),
+        }
+    }
+}
+
+struct Search<'a> {
+    agent: &'a ApproxExpectimaxAgent,
+    // the same boards come up under many different rolls
+    evals: HashMap<BoardKey, f64>,
+}
+
+// Everything on the board an evaluation can see.
+#[derive(PartialEq, Eq, Hash)]
+struct BoardKey {
+    routes: Vec<usize>,
+    round: usize,
+    blocked: Vec<(Tile, Blocker)>,
+    objectives: Vec<(bool, Option<isize>)>,
+}
+
+impl BoardKey {
+    fn new(state: &GameState) -> Self {
+        let mut blocked: Vec<(Tile, Blocker)> = state
+            .blocked_tiles
+            .iter()
+            .map(|(tile, blocker)| (*tile, *blocker))
+            .collect();
+        blocked.sort_unstable_by_key(|(tile, _)| (tile.y, tile.x));
+        Self {
+            routes: route_key(&state.drawn_routes),
+            round: state.round,
+            blocked,
+            objectives: state
+                .objectives
+                .iter()
+                .map(|card| (card.claimed, card.bonus))
+                .collect(),
+        }
+    }
+}
+
+impl<'a> Search<'a> {
+    // Searching every order under every roll is far too slow, so lookahead rounds are drawn
+    // one die at a time, each at the placement that evaluates best.
+    fn best(&mut self, state: &GameState, dice: &[DieFace], depth: usize) -> f64 {
+        let mut state = state.clone();
+        let mut dice: Vec<(DieFace, DieFace)> = dice.iter().map(|face| (*face, *face)).collect();
+        loop {
+            let best = best_legal_action(&state, &dice, |_, action| {
+                let mut next = state.clone();
+                next.apply_route(*action).expect("legal action");
+                self.evaluate(&next)
+            });
+            let Some((i, action)) = best else {
+                break;
+            };
+            state.apply_route(action).expect("legal action");
+            dice.remove(i);
+        }
+        self.after_turn(&state, depth)
+    }
+
+    // Expected value over the next roll, once this round's dice are drawn.
+    fn after_turn(&mut self, state: &GameState, depth: usize) -> f64 {
+        let mut next = state.clone();
+        next.end_turn();
+        if depth == 0 || next.is_over() {
+            return self.evaluate(&next);
+        }
+
+        next.event = None;
+        let mut expected = 0.0;
+        for (dice, probability) in self.agent.outcomes.iter() {
+            next.dice = *dice;
+            expected += probability * self.best(&next, &dice.faces(), depth - 1);
+        }
+        expected
+    }
+
+    fn evaluate(&mut self, state: &GameState) -> f64 {
+        let key = BoardKey::new(state);
+        if let Some(value) = self.evals.get(&key) {
+            return *value;
+        }
+        let value = (self.agent.eval)(state);
+        self.evals.insert(key, value);
+        value
+    }
+}
+
+impl RRIAgent for ApproxExpectimaxAgent {
+    fn prompt(&mut self, state: &GameState) -> Turn {
+        self.best_turn(state, &state.dice.faces())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::puzzle::Puzzle;
+    use crate::rri::{RegularDieFace, SpecialDieFace, EXITS};
+
+    #[test]
+    fn test_outcomes_merge_orderings() {
+        let outcomes = Dice::outcomes();
+        assert_eq!(outcomes.len(), 56 * 3);
+        let total: f64 = outcomes.iter().map(|(_, p)| p).sum();
+        assert!((total - 1.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_boards_that_differ_only_in_blockers_are_evaluated_apart() {
+        let agent =
+            ApproxExpectimaxAgent::new(0, 1, Box::new(|state| state.blocked_tiles.len() as f64));
+        let mut search = Search {
+            agent: &agent,
+            evals: HashMap::new(),
+        };
+        let mut state = GameState::new();
+        assert_eq!(search.evaluate(&state), 0.0);
+        state.block_tile(Tile::CENTER, Blocker::Crater);
+        assert_eq!(search.evaluate(&state), 1.0);
+    }
+
+    #[test]
+    fn test_looks_ahead_to_the_last_round() {
+        // only one exit left open, so the search stays small
+        let mut puzzle = Puzzle::new().round(GameState::ROUNDS - 2).roll(Dice {
+            regular: [
+                RegularDieFace::StraightRail,
+                RegularDieFace::AngleRail,
+                RegularDieFace::StraightRoad,
+            ],
+            special: SpecialDieFace::StraightStation,
+        });
+        for (edge, _) in &EXITS[1..] {
+            puzzle = puzzle.block(edge.tile, Blocker::Crater);
+        }
+        let mut state = puzzle.build().unwrap();
+
+        let result = state.play_turn(&mut ApproxExpectimaxAgent::default());
+        assert_eq!(result.errors, 0);
+        assert!(!result.placed.is_empty());
+        assert!(state.validate().is_ok());
+    }
+}
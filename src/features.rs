@@ -0,0 +1,181 @@
+use crate::agent::{best_legal_action, mean_score};
+use crate::neat::genome::{DomainConfig, Genome};
+use crate::neat::network::{Network, NeuralInterface};
+use crate::rri::{DieFace, Direction, DrawAction, GameState, Piece, RRIAgent, Tile, Turn, EXITS};
+use crate::scoring::ScoreCard;
+use anyhow::{bail, Result};
+use std::borrow::BorrowMut;
+use std::path::Path;
+
+// inputs to the network, per placement
+pub const FEATURES: usize = 21;
+
+// Evaluates each legal placement by a handful of engineered features,
+// so NEAT only has to evolve a small network scoring them.
+// The best placement of any die is drawn, until none of them fit.
+pub struct FeatureAgent<N: BorrowMut<Network>> {
+    network: N,
+}
+
+impl FeatureAgent<Network> {
+    pub const ENCODING: &'static str = "features";
+
+    // Plays a genome saved by training.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let (genome, encoding) = Genome::load(path)?;
+        if encoding != Self::ENCODING {
+            bail!(
+                "Genome was trained with {}, not {}",
+                encoding,
+                Self::ENCODING
+            );
+        }
+        if genome.in_nodes != FEATURES || genome.out_nodes != 1 {
+            bail!(
+                "Genome has {} inputs and {} outputs, expected {} and 1",
+                genome.in_nodes,
+                genome.out_nodes,
+                FEATURES
+            );
+        }
+        Ok(Self::new(Network::new(&genome)?))
+    }
+
+    pub fn config() -> DomainConfig {
+        log::info!("Scoring placements by {} features", FEATURES);
+        DomainConfig {
+            input_layer_size: FEATURES,
+            output_layer_size: 1,
+            encoding: Self::ENCODING.to_string(),
+            fitness: Box::new(|n, seeds| FeatureAgent::new(n).fitness(seeds)),
+        }
+    }
+}
+
+impl<N: BorrowMut<Network>> FeatureAgent<N> {
+    pub fn new(network: N) -> Self {
+        Self { network }
+    }
+
+    pub fn fitness(&mut self, seeds: &[u64]) -> f64 {
+        mean_score(self, seeds)
+    }
+}
+
+impl<N: BorrowMut<Network>> RRIAgent for FeatureAgent<N> {
+    fn prompt(&mut self, state: &GameState) -> Turn {
+        let mut state = state.clone();
+        let mut remaining: Vec<(DieFace, DieFace)> = state
+            .dice
+            .faces()
+            .iter()
+            .map(|face| (*face, *face))
+            .collect();
+        let mut actions = Vec::with_capacity(4);
+        loop {
+            let before = ScoreCard::new(&state);
+            let best = best_legal_action(&state, &remaining, |_, action| {
+                let input = features(&state, &before, *action, remaining.len());
+                self.network.borrow_mut().run(&input)[0]
+            });
+            let Some((i, action)) = best else {
+                return Turn { actions };
+            };
+            state.apply_route(action).expect("legal action");
+            actions.push(action);
+            remaining.remove(i);
+        }
+    }
+}
+
+// What drawing the route would change, roughly scaled to 0..1.
+pub fn features(
+    state: &GameState,
+    before: &ScoreCard,
+    action: DrawAction,
+    dice_left: usize,
+) -> [f64; FEATURES] {
+    let mut after_state = state.clone();
+    after_state
+        .apply_route(action)
+        .expect("features of a legal action");
+    let after = ScoreCard::new(&after_state);
+
+    let Tile { x, y } = action.tile;
+    let on_edge = |v: u8| v == 0 || v == 6;
+    let pattern = action.pattern;
+    let mut neighbors = 0;
+    let mut connections = 0;
+    let mut open_ends = 0;
+    for direction in Direction::ALL {
+        let Some(neighbor) = action.tile.neighbor(direction) else {
+            continue;
+        };
+        let route = state.route_at(neighbor);
+        neighbors += route.is_some() as u8;
+        match (pattern.get(direction), route) {
+            (Some(piece), Some(route)) if route.get(direction.opposite()) == Some(piece) => {
+                connections += 1
+            }
+            (Some(_), None) if state.is_empty(neighbor) => open_ends += 1,
+            _ => {}
+        }
+    }
+    let sides = |piece| {
+        Direction::ALL
+            .iter()
+            .filter(|d| pattern.get(**d) == Some(piece))
+            .count() as f64
+    };
+    let on_exit = EXITS.iter().any(|(edge, piece)| {
+        edge.tile == action.tile && pattern.get(edge.direction) == Some(*piece)
+    });
+    let distance = (x as f64 - 3.0).abs() + (y as f64 - 3.0).abs();
+
+    [
+        1.0, // bias
+        (after.total() - before.total()) as f64 / 10.0,
+        (after.exits - before.exits) as f64 / 10.0,
+        (after.longest_rail - before.longest_rail) as f64,
+        (after.longest_road - before.longest_road) as f64,
+        (after.center - before.center) as f64,
+        (after.errors - before.errors) as f64 / 4.0,
+        (after.objectives - before.objectives) as f64 / 8.0,
+        (after_state.open_edges.len() as f64 - state.open_edges.len() as f64) / 4.0,
+        on_exit as u8 as f64,
+        (on_edge(x) && on_edge(y)) as u8 as f64,
+        (on_edge(x) || on_edge(y)) as u8 as f64,
+        distance / 6.0,
+        neighbors as f64 / 4.0,
+        connections as f64 / 4.0,
+        open_ends as f64 / 4.0,
+        pattern.station as u8 as f64,
+        (pattern.face == DieFace::Overpass) as u8 as f64,
+        sides(Piece::Rail) / 4.0,
+        state.round as f64 / GameState::ROUNDS as f64,
+        dice_left as f64 / 4.0,
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::routes::DIE_PATTERNS;
+
+    #[test]
+    fn test_features_of_exit_route() {
+        let state = GameState::new();
+        let before = ScoreCard::new(&state);
+        let rail = DrawAction {
+            pattern: &DIE_PATTERNS[8],
+            tile: Tile { x: 3, y: 0 },
+        };
+        let features = features(&state, &before, rail, 4);
+
+        // on the north exit, with one error facing the empty tile below
+        assert_eq!(features[9], 1.0);
+        assert_eq!(features[6], 0.25);
+        assert_eq!(features[15], 0.25);
+        assert_eq!(features[18], 0.5);
+    }
+}
@@ -1,8 +1,16 @@
 pub mod agent;
+pub mod expansions;
+pub mod expectimax;
+pub mod features;
 pub mod logger;
+pub mod mcts;
 pub mod neat;
+pub mod objectives;
+pub mod puzzle;
 pub mod routes;
 pub mod rri;
+pub mod scoring;
 pub mod server;
 pub mod templates;
+pub mod tournament;
 pub mod types;
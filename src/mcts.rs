@@ -0,0 +1,478 @@
+use crate::agent::{GreedyAgent, RandomAgent};
+use crate::expansions::EventRoll;
+use crate::rri::{DieFace, DrawAction, GameState, RRIAgent, Turn};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::time::{Duration, Instant};
+
+// Plays out the rest of a game from a leaf of the search tree.
+pub trait DefaultPolicy {
+    // Routes for the dice that are still unused this round.
+    fn complete_turn(&mut self, state: &GameState, dice: &[DieFace]) -> Vec<DrawAction>;
+}
+
+impl<R: Rng> DefaultPolicy for RandomAgent<R> {
+    fn complete_turn(&mut self, state: &GameState, dice: &[DieFace]) -> Vec<DrawAction> {
+        self.random_turn(state, dice).actions
+    }
+}
+
+impl DefaultPolicy for GreedyAgent {
+    fn complete_turn(&mut self, state: &GameState, dice: &[DieFace]) -> Vec<DrawAction> {
+        self.best_turn(state, dice).actions
+    }
+}
+
+// Draws the dice one at a time, each at GreedyAgent's best route for it on its own.
+// Trying every order of the dice like GreedyAgent does takes seconds a game,
+// far too slow for thousands of rollouts.
+#[derive(Default)]
+pub struct GreedyDice {
+    pub greedy: GreedyAgent,
+}
+
+impl DefaultPolicy for GreedyDice {
+    fn complete_turn(&mut self, state: &GameState, dice: &[DieFace]) -> Vec<DrawAction> {
+        let mut state = state.clone();
+        let mut dice = dice.to_vec();
+        let mut actions = Vec::with_capacity(dice.len());
+        while let Some((index, action)) = self.greedy.best_route(&state, &dice) {
+            dice.swap_remove(index);
+            state.apply_route(action).expect("legal action");
+            actions.push(action);
+        }
+        actions
+    }
+}
+
+pub enum Budget {
+    Iterations(usize),
+    Time(Duration),
+}
+
+pub struct MctsConfig {
+    pub budget: Budget,
+    // UCB1 exploration constant, in points of final score
+    pub exploration: f64,
+    // keep the subtree for the roll that actually happened
+    pub reuse_tree: bool,
+}
+
+impl Default for MctsConfig {
+    fn default() -> Self {
+        Self {
+            budget: Budget::Iterations(1000),
+            exploration: 10.0,
+            reuse_tree: true,
+        }
+    }
+}
+
+// Monte Carlo tree search over the rest of the game.
+// Placing a die is a decision node, and the roll at the start of each round is a chance node.
+// The reward is the final score.
+// Greedy dice rollouts are slower than random ones, but random play scores so badly
+// that it barely tells placements apart. At the default budget a game takes minutes.
+pub struct MctsAgent<P: DefaultPolicy = GreedyDice> {
+    pub config: MctsConfig,
+    policy: P,
+    rng: StdRng,
+    // the tree from the last turn, and the chance node its turn ended on.
+    tree: Option<(Tree, NodeId)>,
+}
+
+impl MctsAgent {
+    pub fn seeded(config: MctsConfig, seed: u64) -> Self {
+        Self::new(config, GreedyDice::default(), seed)
+    }
+}
+
+impl<P: DefaultPolicy> MctsAgent<P> {
+    pub fn new(config: MctsConfig, policy: P, seed: u64) -> Self {
+        Self {
+            config,
+            policy,
+            rng: StdRng::seed_from_u64(seed),
+            tree: None,
+        }
+    }
+
+    pub fn search(&mut self, state: &GameState) -> Turn {
+        let mut tree = match self.tree.take() {
+            Some((tree, chance)) if self.config.reuse_tree => tree
+                .reroot(chance, state)
+                .unwrap_or_else(|| Tree::new(state.clone())),
+            _ => Tree::new(state.clone()),
+        };
+
+        let start = Instant::now();
+        let mut iterations = 0;
+        loop {
+            match self.config.budget {
+                Budget::Iterations(limit) if iterations >= limit => break,
+                Budget::Time(limit) if start.elapsed() >= limit => break,
+                _ => {}
+            }
+            self.iterate(&mut tree);
+            iterations += 1;
+        }
+        log::debug!(
+            "MCTS: {} iterations, {} nodes, {:?}",
+            iterations,
+            tree.nodes.len(),
+            start.elapsed()
+        );
+
+        // follow the most visited placements until the round is over
+        let mut actions = Vec::with_capacity(4);
+        let mut id = ROOT;
+        while let Kind::Decision { .. } = tree.nodes[id].kind {
+            let Some((edge, child)) = tree.nodes[id]
+                .children
+                .iter()
+                .max_by_key(|(_, child)| tree.nodes[*child].visits)
+            else {
+                break;
+            };
+            if let Edge::Place(action) = edge {
+                actions.push(*action);
+            }
+            id = *child;
+        }
+        match &tree.nodes[id].kind {
+            // the tree ran out before the round did, so the rollout policy places the rest
+            Kind::Decision { remaining, .. } => {
+                let state = &tree.nodes[id].state;
+                actions.extend(self.policy.complete_turn(state, remaining));
+            }
+            Kind::Chance => self.tree = Some((tree, id)),
+            Kind::Terminal => {}
+        }
+        Turn { actions }
+    }
+
+    fn iterate(&mut self, tree: &mut Tree) {
+        let mut path = vec![ROOT];
+        let mut id = ROOT;
+        // selection and expansion
+        loop {
+            match &mut tree.nodes[id].kind {
+                Kind::Terminal => break,
+                Kind::Decision { untried, .. } if !untried.is_empty() => {
+                    let index = self.rng.gen_range(0..untried.len());
+                    let (face, action) = untried.swap_remove(index);
+                    id = tree.place(id, face, action);
+                    path.push(id);
+                    break;
+                }
+                Kind::Decision { .. } => {
+                    id = self.select(tree, id);
+                    path.push(id);
+                }
+                Kind::Chance => {
+                    let (child, expanded) = self.roll(tree, id);
+                    id = child;
+                    path.push(id);
+                    if expanded {
+                        break;
+                    }
+                }
+            }
+        }
+
+        let reward = self.rollout(&tree.nodes[id]) as f64;
+        for id in path {
+            tree.nodes[id].visits += 1;
+            tree.nodes[id].total += reward;
+        }
+    }
+
+    // Samples the next roll, and whether its node is new.
+    fn roll(&mut self, tree: &mut Tree, id: NodeId) -> (NodeId, bool) {
+        let mut state = tree.nodes[id].state.clone();
+        state.dice.roll_with(&mut self.rng);
+        state.event = state.variant.roll_event(&mut self.rng);
+        let outcome = Outcome::new(&state);
+        let existing = tree.nodes[id]
+            .children
+            .iter()
+            .find(|(edge, _)| *edge == Edge::Roll(outcome));
+        if let Some((_, child)) = existing {
+            return (*child, false);
+        }
+
+        // events are only sampled once per outcome
+        if let Some(event) = state.event {
+            state.resolve_event(event, &mut self.rng);
+        }
+        let dice = state.dice.faces().to_vec();
+        let child = tree.push(state, dice);
+        tree.nodes[id].children.push((Edge::Roll(outcome), child));
+        (child, true)
+    }
+
+    // UCB1 over the children of a decision node.
+    fn select(&self, tree: &Tree, id: NodeId) -> NodeId {
+        let node = &tree.nodes[id];
+        let parent_visits = (node.visits.max(1) as f64).ln();
+        node.children
+            .iter()
+            .map(|(_, child)| *child)
+            .max_by(|a, b| {
+                let ucb = |id: NodeId| {
+                    let child = &tree.nodes[id];
+                    let visits = child.visits.max(1) as f64;
+                    child.total / visits + self.config.exploration * (parent_visits / visits).sqrt()
+                };
+                ucb(*a).total_cmp(&ucb(*b))
+            })
+            .expect("fully expanded decision nodes have children")
+    }
+
+    fn rollout(&mut self, node: &Node) -> isize {
+        let mut state = node.state.clone();
+        if let Kind::Decision { remaining, .. } = &node.kind {
+            for action in self.policy.complete_turn(&state, remaining) {
+                state.apply_route(action).expect("legal action");
+            }
+            state.end_turn();
+        }
+        while !state.is_over() {
+            state.dice.roll_with(&mut self.rng);
+            state.event = state.variant.roll_event(&mut self.rng);
+            if let Some(event) = state.event {
+                state.resolve_event(event, &mut self.rng);
+            }
+            for action in self.policy.complete_turn(&state, &state.dice.faces()) {
+                state.apply_route(action).expect("legal action");
+            }
+            state.end_turn();
+        }
+        state.score()
+    }
+}
+
+impl<P: DefaultPolicy> RRIAgent for MctsAgent<P> {
+    fn prompt(&mut self, state: &GameState) -> Turn {
+        self.search(state)
+    }
+
+    // the tree no longer matches the board, so the next turn searches from scratch
+    fn handle_error(&mut self, error: anyhow::Error) {
+        log::error!("MctsAgent: {}", error);
+        self.tree = None;
+    }
+}
+
+type NodeId = usize;
+const ROOT: NodeId = 0;
+
+// The dice are unordered, so rolls are identified by their sorted faces.
+#[derive(Clone, Copy, PartialEq, Eq)]
+struct Outcome {
+    faces: [DieFace; 4],
+    event: Option<EventRoll>,
+}
+
+impl Outcome {
+    fn new(state: &GameState) -> Self {
+        let mut faces = state.dice.faces();
+        faces[..3].sort_unstable();
+        Self {
+            faces,
+            event: state.event,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Edge {
+    Place(DrawAction),
+    Roll(Outcome),
+}
+
+enum Kind {
+    Decision {
+        // dice not drawn yet this round
+        remaining: Vec<DieFace>,
+        untried: Vec<(DieFace, DrawAction)>,
+    },
+    // the round is over, the next node depends on the roll
+    Chance,
+    Terminal,
+}
+
+struct Node {
+    state: GameState,
+    kind: Kind,
+    visits: u32,
+    total: f64,
+    children: Vec<(Edge, NodeId)>,
+}
+
+struct Tree {
+    nodes: Vec<Node>,
+}
+
+impl Tree {
+    fn new(state: GameState) -> Self {
+        let mut tree = Self { nodes: vec![] };
+        let dice = state.dice.faces().to_vec();
+        tree.push(state, dice);
+        tree
+    }
+
+    // Adds the node for a board with dice left to draw.
+    // When none of them fit, the round is over.
+    fn push(&mut self, mut state: GameState, remaining: Vec<DieFace>) -> NodeId {
+        let mut untried = Vec::new();
+        for (i, face) in remaining.iter().enumerate() {
+            if remaining[..i].contains(face) {
+                continue;
+            }
+            for action in state.legal_actions(*face) {
+                untried.push((*face, action));
+            }
+        }
+
+        let kind = if !untried.is_empty() {
+            Kind::Decision { remaining, untried }
+        } else {
+            state.end_turn();
+            if state.is_over() {
+                Kind::Terminal
+            } else {
+                Kind::Chance
+            }
+        };
+        self.nodes.push(Node {
+            state,
+            kind,
+            visits: 0,
+            total: 0.0,
+            children: vec![],
+        });
+        self.nodes.len() - 1
+    }
+
+    fn place(&mut self, id: NodeId, face: DieFace, action: DrawAction) -> NodeId {
+        let node = &self.nodes[id];
+        let Kind::Decision { remaining, .. } = &node.kind else {
+            unreachable!("only decision nodes place routes");
+        };
+        let mut remaining = remaining.clone();
+        let index = remaining.iter().position(|f| *f == face).expect("rolled");
+        remaining.swap_remove(index);
+        let mut state = node.state.clone();
+        state.apply_route(action).expect("legal action");
+
+        let child = self.push(state, remaining);
+        self.nodes[id].children.push((Edge::Place(action), child));
+        child
+    }
+
+    // The subtree for the roll that happened after the chance node, as a new tree.
+    fn reroot(self, chance: NodeId, state: &GameState) -> Option<Tree> {
+        let outcome = Outcome::new(state);
+        let root = self.nodes[chance]
+            .children
+            .iter()
+            .find(|(edge, _)| *edge == Edge::Roll(outcome))
+            .map(|(_, child)| *child)?;
+        let expected = &self.nodes[root].state;
+        let same_board = expected.round == state.round
+            && expected.drawn_routes.len() == state.drawn_routes.len()
+            && expected.open_edges == state.open_edges
+            && expected.blocked_tiles == state.blocked_tiles;
+        if !same_board {
+            return None;
+        }
+
+        let mut nodes: Vec<Option<Node>> = self.nodes.into_iter().map(Some).collect();
+        let mut tree = Tree { nodes: vec![] };
+        let mut stack: Vec<(NodeId, Option<(NodeId, Edge)>)> = vec![(root, None)];
+        while let Some((old, parent)) = stack.pop() {
+            let mut node = nodes[old].take().expect("trees don't share nodes");
+            let children = std::mem::take(&mut node.children);
+            tree.nodes.push(node);
+            let id = tree.nodes.len() - 1;
+            if let Some((parent, edge)) = parent {
+                tree.nodes[parent].children.push((edge, id));
+            }
+            for (edge, child) in children {
+                stack.push((child, Some((id, edge))));
+            }
+        }
+        Some(tree)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::expansions::Blocker;
+    use crate::puzzle::Puzzle;
+    use crate::rri::EXITS;
+
+    fn agent(iterations: usize) -> MctsAgent {
+        MctsAgent::seeded(
+            MctsConfig {
+                budget: Budget::Iterations(iterations),
+                ..Default::default()
+            },
+            3,
+        )
+    }
+
+    #[test]
+    fn test_mcts_plays_legal_game() {
+        let mut agent = agent(10);
+        let mut game = GameState::new();
+        for result in game.play(&mut agent) {
+            assert_eq!(result.errors, 0);
+        }
+        assert!(game.validate().is_ok());
+    }
+
+    #[test]
+    fn test_turn_places_every_die_that_fits() {
+        // too few iterations to reach the end of the round in the tree
+        let mut agent = agent(3);
+        let mut rng = StdRng::seed_from_u64(5);
+        let mut game = GameState::new();
+        while !game.is_over() {
+            game.dice.roll_with(&mut rng);
+            let turn = agent.search(&game);
+            let mut unused = game.dice.faces().to_vec();
+            for action in turn.actions {
+                let index = unused.iter().position(|f| *f == action.pattern.face);
+                unused.swap_remove(index.expect("rolled"));
+                game.apply_route(action).unwrap();
+            }
+            for face in unused {
+                assert!(game.legal_actions(face).is_empty());
+            }
+            game.end_turn();
+        }
+    }
+
+    #[test]
+    fn test_tree_is_reused_for_the_next_roll() {
+        // with every exit cratered nothing fits, so each turn is just a roll
+        let mut puzzle = Puzzle::new();
+        for (edge, _) in EXITS {
+            puzzle = puzzle.block(edge.tile, Blocker::Crater);
+        }
+        let state = puzzle.build().unwrap();
+        let mut agent = agent(200);
+        assert!(agent.search(&state).actions.is_empty());
+
+        let (tree, chance) = agent.tree.take().expect("turn ends with a roll");
+        let (_, child) = tree.nodes[chance].children[0];
+        let visits = tree.nodes[child].visits;
+        let next = tree.nodes[child].state.clone();
+
+        let tree = tree.reroot(chance, &next).expect("same board and roll");
+        assert_eq!(tree.nodes[ROOT].visits, visits);
+    }
+}
@@ -1,21 +1,33 @@
 use super::network::*;
-use anyhow::Result;
+use super::report::{GenerationReport, MutationCounts, SpeciesReport, Stats, Timings};
+use anyhow::{bail, Result};
 use decorum::R64;
 use rand::seq::SliceRandom;
-use rand::Rng;
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha12Rng;
 use rand_distr::Normal;
-use std::collections::HashMap;
+use serde::{Deserialize, Serialize};
+use serde_with::serde_as;
+use std::collections::{HashMap, HashSet};
 use std::fs::{self};
 use std::io::Write;
 use std::ops::Range;
+use std::path::{Path, PathBuf};
 use std::process::Command;
-use std::rc::Rc;
-use std::usize;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::Instant;
+
+// Scores the network over one game, or test case, per seed.
+pub type FitnessFn = Box<dyn Fn(&mut Network, &[u64]) -> f64 + Send + Sync>;
 
 pub struct DomainConfig {
     pub input_layer_size: usize,
     pub output_layer_size: usize,
-    pub fitness: Box<dyn Fn(&mut Network) -> f64>,
+    // names how inputs and outputs are read, saved with exported genomes
+    pub encoding: String,
+    pub fitness: FitnessFn,
 }
 
 #[derive(Clone, Debug)]
@@ -56,23 +68,63 @@ pub struct Parameters {
     pub mutation_rate: f64,
     // percentage allowed to recombine
     pub reproduction_rate: f64,
+    // chance a disabled gene is enabled again in a crossover child
+    pub reenable_rate: f64,
+    // best genomes of each species carried over unchanged
+    pub elitism: usize,
+    // offspring every species gets, while there's room for it in the population
+    pub min_species_size: usize,
+    // generations without improvement before a species is culled
+    pub stagnation: usize,
+    // the best species by fitness are never culled
+    pub protected_species: usize,
+    // seeds every genome plays in a generation, so they're compared on the same dice
+    pub games: usize,
+    // seeds the champion is scored on, fixed for the whole run
+    pub held_out_games: usize,
+    // for repeatable runs, whatever the number of workers
+    pub seed: Option<u64>,
+    // threads evaluating fitness
+    pub workers: usize,
+    pub checkpointing: Option<Checkpointing>,
+}
+
+// Saves the population every so many generations, so a run can be resumed with Population::load.
+pub struct Checkpointing {
+    pub every: usize,
+    pub path: PathBuf,
 }
-impl Parameters {
-    pub fn default() -> Self {
+impl Default for Parameters {
+    fn default() -> Self {
         Self {
             reproduction_rate: 0.5,
+            reenable_rate: 0.25,
             mutation_rate: 0.3,
+            elitism: 1,
+            min_species_size: 2,
+            stagnation: 15,
+            protected_species: 2,
             population: 100,
+            games: 100,
+            held_out_games: 100,
+            seed: None,
+            workers: thread::available_parallelism().map_or(1, |n| n.get()),
+            checkpointing: None,
             mutation: MutationWeights::new([
                 (Mutation::AdjustWeight, 18),
+                (Mutation::ReplaceWeight, 1),
                 (Mutation::AddGene, 1),
                 (Mutation::AddNode, 1),
+                (Mutation::ToggleEnabled, 1),
+                (Mutation::DeleteGene, 1),
+                (Mutation::DeleteNode, 1),
             ]),
             speciation: Speciation {
                 c1: 1.0,
                 c2: 1.0,
                 c3: 3.0,
                 ct: 4.0,
+                control: None,
             },
         }
     }
@@ -96,10 +148,10 @@ impl MutationWeights {
         Self { storage }
     }
 
-    pub fn sample(&self) -> Mutation {
+    pub fn sample<R: Rng + ?Sized>(&self, rng: &mut R) -> Mutation {
         *self
             .storage
-            .choose(&mut rand::thread_rng())
+            .choose(rng)
             .expect("distribution should not be empty")
     }
 }
@@ -107,16 +159,27 @@ impl MutationWeights {
 #[derive(Clone, Copy, Debug)]
 pub enum Mutation {
     AdjustWeight,
+    // a fresh weight, rather than a nudge
+    ReplaceWeight,
     AddNode,
     AddGene,
+    ToggleEnabled,
+    DeleteGene,
+    // along with its genes
+    DeleteNode,
 }
 
-#[derive(Default, Clone, Debug)]
+#[derive(Default, Clone, Debug, Serialize, Deserialize)]
 pub struct Fitness {
     pub actual: f64,
     pub adjusted: f64,
+    // only scored for champions
+    pub held_out: Option<f64>,
 }
+#[serde_as]
+#[derive(Default, Clone, Serialize, Deserialize)]
 pub struct Genes {
+    #[serde_as(as = "Vec<(_, _)>")]
     map: HashMap<(NodeId, NodeId), GeneId>,
 }
 
@@ -130,9 +193,18 @@ impl Genes {
         self.map.iter()
     }
 
-    pub fn insert(&mut self, in_node: NodeId, out_node: NodeId) {
+    // Id of the connection, which is a new one if it's never been seen before.
+    pub fn insert(&mut self, in_node: NodeId, out_node: NodeId) -> GeneId {
         let gene_id = GeneId(self.map.len());
-        self.map.insert((in_node, out_node), gene_id);
+        *self.map.entry((in_node, out_node)).or_insert(gene_id)
+    }
+
+    // what insert would give, without recording it
+    pub fn id(&self, in_node: NodeId, out_node: NodeId) -> GeneId {
+        self.map
+            .get(&(in_node, out_node))
+            .copied()
+            .unwrap_or(GeneId(self.map.len()))
     }
 
     pub fn contains(&self, a: NodeId, b: NodeId) -> bool {
@@ -144,13 +216,25 @@ impl Genes {
     }
 }
 
+// Structural mutations made this generation, so genomes making the same one get the same ids.
+// New connections are looked up in Genes, which keeps them for the whole run.
+#[derive(Default)]
+struct Innovations {
+    // gene split by AddNode, and the node put in its place
+    splits: HashMap<GeneId, NodeId>,
+}
+
 pub struct Population {
     pub generation: usize,
     pub config: Config,
     pub champion: ScoredGenome,
-    pub genomes: Vec<Rc<Genome>>,
+    pub genomes: Vec<Arc<Genome>>,
     pub genes: Genes,
     pub node_count: usize,
+    pub species: Vec<Species>,
+    next_species_id: usize,
+    rng: ChaCha12Rng,
+    held_out: Vec<u64>,
 }
 
 impl Population {
@@ -164,26 +248,26 @@ impl Population {
             .create(true)
             .open("graphviz/pop.dot")?;
         let indent = "";
-        write!(&mut file, "strict digraph {{\n")?;
-        write!(&mut file, "{indent: <2}subgraph {{\n")?;
+        writeln!(&mut file, "strict digraph {{")?;
+        writeln!(&mut file, "{indent: <2}subgraph {{")?;
         write!(&mut file, "{indent: <4}rank=min;\n{indent: <4}")?;
         let node_counts = self.node_counts();
         for node_index in 0..node_counts.in_nodes {
             write!(&mut file, "{}; ", node_index)?;
         }
-        write!(&mut file, "\n{indent: <2}}}\n")?;
+        writeln!(&mut file, "\n{indent: <2}}}")?;
 
-        write!(&mut file, "{indent: <2}subgraph {{\n")?;
+        writeln!(&mut file, "{indent: <2}subgraph {{")?;
         write!(&mut file, "{indent: <4}rank=max;\n{indent: <4}")?;
         for node_index in node_counts.in_nodes..node_counts.in_nodes + node_counts.out_nodes {
             write!(&mut file, "{}; ", node_index)?;
         }
-        write!(&mut file, "\n{indent: <2}}}\n")?;
+        writeln!(&mut file, "\n{indent: <2}}}")?;
 
         for ((node1, node2), gene) in self.genes.iter() {
-            write!(
+            writeln!(
                 &mut file,
-                "{indent: <2}{} -> {} [label=\"{}\"]\n",
+                "{indent: <2}{} -> {} [label=\"{}\"]",
                 node1.0, node2.0, gene.0,
             )?;
         }
@@ -201,12 +285,23 @@ impl Population {
 
     pub fn new(config: Config) -> Population {
         let node_count = config.domain.input_layer_size + config.domain.output_layer_size;
-        let initial_genome = Rc::new(Genome::new(&config.domain));
+        let initial_genome = Arc::new(Genome::new(&config.domain));
         let population = vec![initial_genome; config.parameters.population];
         let champion = population[0].clone();
+        let mut rng = match config.parameters.seed {
+            Some(seed) => ChaCha12Rng::seed_from_u64(seed),
+            None => ChaCha12Rng::from_entropy(),
+        };
+        let held_out = (0..config.parameters.held_out_games)
+            .map(|_| rng.gen())
+            .collect();
         Self {
             generation: 0,
             node_count,
+            species: Vec::new(),
+            next_species_id: 0,
+            rng,
+            held_out,
             genomes: population,
             config,
             genes: Genes::new(),
@@ -225,90 +320,368 @@ impl Population {
         }
     }
 
-    fn classify_species(&self) -> Vec<Species> {
-        let mut groups: Vec<Species> = vec![];
+    // Genomes join the first species whose representative, from the last generation, they're
+    // compatible with. Species nobody joined die out.
+    fn classify_species(&mut self) -> Vec<Species> {
+        let mut groups = std::mem::take(&mut self.species);
+        for species in groups.iter_mut() {
+            species.genomes.clear();
+        }
         'outer: for genome in self.genomes.iter() {
             for species in groups.iter_mut() {
-                let rep = species
-                    .genomes
-                    .choose(&mut rand::thread_rng())
-                    .expect("Species group should not be empty");
-                if self.config.parameters.speciation.compatible(genome, rep) {
+                if self
+                    .config
+                    .parameters
+                    .speciation
+                    .compatible(genome, &species.representative)
+                {
                     species.genomes.push(genome.clone());
                     continue 'outer;
                 }
             }
-            groups.push(Species {
-                genomes: vec![genome.clone()],
-            });
+            groups.push(Species::new(self.next_species_id, genome.clone()));
+            self.next_species_id += 1;
         }
+        groups.retain(|species| !species.genomes.is_empty());
         log::debug!("Classified into {} species", groups.len());
         groups
     }
 
-    pub fn advance_gen(&mut self) {
-        let groups = self.classify_species();
-        let mut total_fitness: f64 = 0.0;
+    pub fn advance_gen(&mut self) -> GenerationReport {
+        let start = Instant::now();
+        let mut timings = Timings::default();
+        let mut groups = self.classify_species();
+        let threshold = self.config.parameters.speciation.threshold();
+        self.config.parameters.speciation.adjust(groups.len());
+        timings.speciate = start.elapsed().as_secs_f64();
+
+        let seeds: Vec<u64> = (0..self.config.parameters.games)
+            .map(|_| self.rng.gen())
+            .collect();
+        let mut best: Option<ScoredGenome> = None;
         let mut group_fitness: Vec<f64> = vec![0.0; groups.len()];
         let mut individual_fitness: Vec<Vec<ScoredGenome>> = Vec::with_capacity(groups.len());
 
+        let genomes: Vec<Arc<Genome>> = groups
+            .iter()
+            .flat_map(|species| species.genomes.iter().cloned())
+            .collect();
+        let timer = Instant::now();
+        let mut scores = self.evaluate(&genomes, &seeds).into_iter();
+        timings.evaluate = timer.elapsed().as_secs_f64();
+
         for (j, species) in groups.iter().enumerate() {
             individual_fitness.push(Vec::with_capacity(species.genomes.len()));
             for genome in species.genomes.iter() {
-                let mut network = Network::new(genome).expect("valid network");
-                let actual = (self.config.domain.fitness)(&mut network);
+                let actual = scores.next().expect("a score for every genome");
                 let adjusted = actual / species.genomes.len() as f64;
                 let scored = ScoredGenome {
-                    fitness: Fitness { actual, adjusted },
+                    fitness: Fitness {
+                        actual,
+                        adjusted,
+                        held_out: None,
+                    },
                     genome: genome.clone(),
                 };
 
-                if scored.fitness.actual > self.champion.fitness.actual {
-                    log::info!(
-                        "New champion (Gen {}): actual: {actual}, adjusted: {adjusted}",
-                        self.generation
-                    );
-                    self.champion = scored.clone();
+                if best
+                    .as_ref()
+                    .is_none_or(|best| scored.fitness.actual > best.fitness.actual)
+                {
+                    best = Some(scored.clone());
                 }
 
-                total_fitness += adjusted;
                 group_fitness[j] += adjusted;
                 individual_fitness[j].push(scored);
             }
         }
 
-        let average_fitness: f64 = total_fitness / self.config.parameters.population as f64;
+        let timer = Instant::now();
+        if let Some(best) = best {
+            self.challenge(best);
+        }
+        timings.challenge = timer.elapsed().as_secs_f64();
+
+        for (species, genomes) in groups.iter_mut().zip(individual_fitness.iter()) {
+            let best = genomes
+                .iter()
+                .map(|g| g.fitness.actual)
+                .fold(f64::NEG_INFINITY, f64::max);
+            species.improve(best);
+        }
+        let culled = self.stagnant_species(&groups);
+
+        let fitness = |f: fn(&Fitness) -> f64| -> Vec<f64> {
+            individual_fitness
+                .iter()
+                .flatten()
+                .map(|g| f(&g.fitness))
+                .collect()
+        };
+        let population = genomes.len().max(1) as f64;
+        let mut report = GenerationReport {
+            generation: self.generation,
+            actual: Stats::new(&fitness(|f| f.actual)),
+            adjusted: Stats::new(&fitness(|f| f.adjusted)),
+            species: Vec::with_capacity(groups.len()),
+            threshold,
+            mean_genes: genomes.iter().map(|g| g.genes.len()).sum::<usize>() as f64 / population,
+            mean_hidden_nodes: genomes.iter().map(|g| g.hidden_nodes.len()).sum::<usize>() as f64
+                / population,
+            mutations: MutationCounts::default(),
+            timings,
+        };
 
-        self.genomes = Vec::with_capacity(self.config.parameters.population);
-        for (j, species) in individual_fitness.into_iter().enumerate() {
-            let mut genomes = species;
+        // stagnant species get no offspring
+        let survivors: Vec<usize> = (0..groups.len()).filter(|j| !culled[*j]).collect();
+        let shares: Vec<f64> = survivors.iter().map(|j| group_fitness[*j]).collect();
+        let parameters = &self.config.parameters;
+        let mut offspring = vec![0; groups.len()];
+        let allocated =
+            allocate_offspring(&shares, parameters.population, parameters.min_species_size);
+        for (j, count) in survivors.into_iter().zip(allocated) {
+            offspring[j] = count;
+        }
 
-            genomes.sort_unstable_by_key(|g| R64::from(g.fitness.actual));
-            let new_pop_size = (group_fitness[j] / average_fitness).ceil() as usize;
+        let timer = Instant::now();
+        self.genomes = Vec::with_capacity(self.config.parameters.population);
+        let mut elites = Vec::new();
+        for (j, mut genomes) in individual_fitness.into_iter().enumerate() {
+            // best first
+            genomes.sort_unstable_by_key(|g| std::cmp::Reverse(R64::from(g.fitness.actual)));
+            let elite = self.config.parameters.elitism.min(offspring[j]);
             let group_size: f64 = genomes.len() as f64;
             let parents = (group_size * self.config.parameters.reproduction_rate).ceil() as usize;
 
-            log::debug!("species: {j}, pop: {} -> {}", genomes.len(), new_pop_size);
-            self.reproduce(&mut genomes[0..parents], new_pop_size);
+            let species = &groups[j];
+            log::debug!(
+                "species: {}, pop: {} -> {}",
+                species.id,
+                genomes.len(),
+                offspring[j]
+            );
+            report.species.push(SpeciesReport {
+                id: species.id,
+                age: species.age,
+                stagnant: species.stagnant,
+                size: genomes.len(),
+                offspring: offspring[j],
+            });
+            elites.extend(genomes.iter().take(elite).map(|g| g.genome.clone()));
+            self.reproduce(&mut genomes[0..parents], offspring[j] - elite);
         }
-
-        self.mutate_population();
+        report.timings.reproduce = timer.elapsed().as_secs_f64();
+
+        // next generation is compared against a member of this one
+        for species in groups.iter_mut() {
+            species.representative = species
+                .genomes
+                .choose(&mut self.rng)
+                .expect("Species group should not be empty")
+                .clone();
+        }
+        self.species = groups
+            .into_iter()
+            .zip(culled)
+            .filter(|(_, culled)| !culled)
+            .map(|(species, _)| species)
+            .collect();
+
+        let timer = Instant::now();
+        report.mutations = self.mutate_population();
+        report.timings.mutate = timer.elapsed().as_secs_f64();
+        // added after mutating, so they're kept as they were
+        self.genomes.extend(elites);
         self.generation += 1;
+
+        if let Some(checkpointing) = &self.config.parameters.checkpointing {
+            if self.generation.is_multiple_of(checkpointing.every.max(1)) {
+                if let Err(error) = self.save(&checkpointing.path) {
+                    log::error!("Failed to save checkpoint: {}", error);
+                }
+            }
+        }
+        report.timings.total = start.elapsed().as_secs_f64();
+        report
+    }
+
+    // Everything needed to carry on exactly where the run stopped, except the config.
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<()> {
+        let path = path.as_ref();
+        let checkpoint = Checkpoint {
+            version: Checkpoint::VERSION,
+            generation: self.generation,
+            champion: self.champion.clone(),
+            genomes: self.genomes.clone(),
+            genes: self.genes.clone(),
+            node_count: self.node_count,
+            species: self.species.clone(),
+            next_species_id: self.next_species_id,
+            threshold: self.config.parameters.speciation.ct,
+            rng: self.rng.clone(),
+            held_out: self.held_out.clone(),
+        };
+        // written alongside first, so being killed mid write doesn't lose the last checkpoint
+        let partial = path.with_extension("partial");
+        fs::write(&partial, serde_json::to_vec(&checkpoint)?)?;
+        fs::rename(&partial, path)?;
+        log::info!("Saved generation {} to {}", self.generation, path.display());
+        Ok(())
+    }
+
+    // Exports the champion, to be played outside of training.
+    pub fn save_champion(&self, path: impl AsRef<Path>) -> Result<()> {
+        let path = path.as_ref();
+        self.champion
+            .genome
+            .save(path, &self.config.domain.encoding)?;
+        log::info!(
+            "Saved champion of generation {} to {}",
+            self.generation,
+            path.display()
+        );
+        Ok(())
+    }
+
+    // Carries on from the checkpoint, if there is one.
+    pub fn resume(config: Config) -> Result<Population> {
+        match &config.parameters.checkpointing {
+            Some(checkpointing) if checkpointing.path.exists() => {
+                let path = checkpointing.path.clone();
+                log::info!("Resuming from {}", path.display());
+                Self::load(path, config)
+            }
+            _ => Ok(Self::new(config)),
+        }
+    }
+
+    // The fitness function can't be saved, so the config is supplied again.
+    pub fn load(path: impl AsRef<Path>, mut config: Config) -> Result<Population> {
+        let checkpoint: Checkpoint = serde_json::from_slice(&fs::read(path)?)?;
+        if checkpoint.version != Checkpoint::VERSION {
+            bail!(
+                "Checkpoint version {} can't be loaded, expected {}",
+                checkpoint.version,
+                Checkpoint::VERSION
+            );
+        }
+        let domain = &config.domain;
+        if let Some(genome) = checkpoint.genomes.iter().find(|genome| {
+            genome.in_nodes != domain.input_layer_size
+                || genome.out_nodes != domain.output_layer_size
+        }) {
+            bail!(
+                "Checkpoint has {} inputs and {} outputs, the config has {} and {}",
+                genome.in_nodes,
+                genome.out_nodes,
+                domain.input_layer_size,
+                domain.output_layer_size
+            );
+        }
+        config.parameters.speciation.ct = checkpoint.threshold;
+        Ok(Self {
+            generation: checkpoint.generation,
+            config,
+            champion: checkpoint.champion,
+            genomes: checkpoint.genomes,
+            genes: checkpoint.genes,
+            node_count: checkpoint.node_count,
+            species: checkpoint.species,
+            next_species_id: checkpoint.next_species_id,
+            rng: checkpoint.rng,
+            held_out: checkpoint.held_out,
+        })
+    }
+
+    // Fitness of each genome, in order, with the work spread over the workers.
+    // Genomes are scored independently on the same seeds, so the number of workers doesn't matter.
+    fn evaluate(&self, genomes: &[Arc<Genome>], seeds: &[u64]) -> Vec<f64> {
+        let fitness = &self.config.domain.fitness;
+        let next = AtomicUsize::new(0);
+        thread::scope(|scope| {
+            let workers: Vec<_> = (0..self.config.parameters.workers.max(1))
+                .map(|_| {
+                    scope.spawn(|| {
+                        let mut scores = Vec::new();
+                        loop {
+                            let i = next.fetch_add(1, Ordering::Relaxed);
+                            let Some(genome) = genomes.get(i) else {
+                                return scores;
+                            };
+                            let mut network = Network::new(genome).expect("valid network");
+                            scores.push((i, fitness(&mut network, seeds)));
+                        }
+                    })
+                })
+                .collect();
+
+            let mut scores = vec![0.0; genomes.len()];
+            for worker in workers {
+                for (i, score) in worker.join().expect("fitness worker panicked") {
+                    scores[i] = score;
+                }
+            }
+            scores
+        })
+    }
+
+    // The generation's best only takes over if it also beats the champion on the held out seeds,
+    // so a genome that got lucky dice once can't hold on to the title.
+    fn challenge(&mut self, mut challenger: ScoredGenome) {
+        let mut network = Network::new(&challenger.genome).expect("valid network");
+        let held_out = (self.config.domain.fitness)(&mut network, &self.held_out);
+        challenger.fitness.held_out = Some(held_out);
+        if self
+            .champion
+            .fitness
+            .held_out
+            .is_none_or(|champion| held_out > champion)
+        {
+            log::info!(
+                "New champion (Gen {}): actual: {}, held out: {held_out}",
+                self.generation,
+                challenger.fitness.actual
+            );
+            self.champion = challenger;
+        }
+    }
+
+    // Species that haven't improved in too long, except the best few, which are always kept.
+    fn stagnant_species(&self, groups: &[Species]) -> Vec<bool> {
+        let parameters = &self.config.parameters;
+        let mut order: Vec<usize> = (0..groups.len()).collect();
+        order.sort_by(|a, b| groups[*b].best_fitness.total_cmp(&groups[*a].best_fitness));
+        let mut culled = vec![false; groups.len()];
+        // the best species is kept regardless, so the population can't die out
+        for j in order.into_iter().skip(parameters.protected_species.max(1)) {
+            if groups[j].stagnant >= parameters.stagnation {
+                log::debug!(
+                    "Culling species {}, no improvement in {} generations",
+                    groups[j].id,
+                    groups[j].stagnant
+                );
+                culled[j] = true;
+            }
+        }
+        culled
     }
 
     fn reproduce(&mut self, parents: &mut [ScoredGenome], target_size: usize) {
         let mut remaining = target_size;
+        if remaining == 0 {
+            return;
+        }
         loop {
             log::trace!("loop: reproduce");
-            parents.shuffle(&mut rand::thread_rng());
+            parents.shuffle(&mut self.rng);
             for chunk in parents.chunks(2) {
                 if chunk.get(1).is_none() {
                     // copy directly
                     self.genomes.push(chunk[0].genome.clone());
                 } else {
                     // crossover 2 genomes
-                    self.genomes
-                        .push(Rc::new(self.crossover(&chunk[0], &chunk[1])));
+                    let child = self.crossover(&chunk[0], &chunk[1]);
+                    self.genomes.push(Arc::new(child));
                 }
                 remaining -= 1;
                 if remaining == 0 {
@@ -318,19 +691,38 @@ impl Population {
         }
     }
 
-    fn crossover(&self, a: &ScoredGenome, b: &ScoredGenome) -> Genome {
-        let genome = Genome {
+    fn crossover(&mut self, a: &ScoredGenome, b: &ScoredGenome) -> Genome {
+        let mut genome = Genome {
             genes: self.merge_genes(a, b),
             hidden_nodes: self.merge_nodes(a, b),
             in_nodes: a.genome.in_nodes,
             out_nodes: a.genome.out_nodes,
         };
 
-        if cfg!(debug_assertions) {
-            if let Err(error) = Network::new(&genome) {
-                log::error!("{}", error);
-                log::info!("a: {:?}\nb: {:?}", a.genome, b.genome);
-                panic!();
+        // the parents' connections can make a cycle or a dead node between them,
+        // the fitter parent is kept instead
+        if genome.has_cycle() || Network::new(&genome).is_err() {
+            log::debug!("Crossover failed, copying the fitter parent");
+            let fitter = if a.fitness.actual > b.fitness.actual {
+                a
+            } else {
+                b
+            };
+            return (*fitter.genome).clone();
+        }
+
+        for at in 0..genome.genes.len() {
+            let gene = &genome.genes[at];
+            if gene.enabled
+                || !self.rng.gen_bool(self.config.parameters.reenable_rate)
+                || genome.reaches(gene.out_node, gene.in_node)
+            {
+                continue;
+            }
+            genome.toggle(at);
+            // it can still leave a dead node
+            if Network::new(&genome).is_err() {
+                genome.toggle(at);
             }
         }
         genome
@@ -378,7 +770,7 @@ impl Population {
         hidden_nodes
     }
 
-    fn merge_genes(&self, a: &ScoredGenome, b: &ScoredGenome) -> Vec<Rc<Gene>> {
+    fn merge_genes(&self, a: &ScoredGenome, b: &ScoredGenome) -> Vec<Arc<Gene>> {
         let mut i = 0;
         let mut j = 0;
         let mut genes = Vec::with_capacity(a.genome.genes.len() + b.genome.genes.len());
@@ -424,114 +816,286 @@ impl Population {
     }
 
     // mutate the whole population.
-    fn mutate_population(&mut self) {
-        self.genomes.shuffle(&mut rand::thread_rng());
+    fn mutate_population(&mut self) -> MutationCounts {
+        let mut counts = MutationCounts::default();
+        let mut innovations = Innovations::default();
+        self.genomes.shuffle(&mut self.rng);
         let count =
             (self.config.parameters.mutation_rate * self.genomes.len() as f64).ceil() as usize;
 
         for genome_index in 0..count {
-            let genome = Rc::make_mut(&mut self.genomes[genome_index]);
-            match self.config.parameters.mutation.sample() {
+            let genome = Arc::make_mut(&mut self.genomes[genome_index]);
+            let mutation = self.config.parameters.mutation.sample(&mut self.rng);
+            match mutation {
                 Mutation::AdjustWeight => {
-                    if let Some(gene) = genome.genes.choose_mut(&mut rand::thread_rng()) {
-                        let gene = Rc::make_mut(gene);
+                    if let Some(gene) = genome.genes.choose_mut(&mut self.rng) {
+                        let gene = Arc::make_mut(gene);
 
                         let distribution = Normal::new(0.0, 0.1).unwrap();
-                        gene.weight += rand::thread_rng().sample::<f64, _>(distribution);
+                        gene.weight += self.rng.sample::<f64, _>(distribution);
+                        counts.record(mutation);
+                    }
+                }
+
+                Mutation::ReplaceWeight => {
+                    if let Some(gene) = genome.genes.choose_mut(&mut self.rng) {
+                        let gene = Arc::make_mut(gene);
+
+                        let distribution = Normal::new(0.0, 1.0).unwrap();
+                        gene.weight = self.rng.sample::<f64, _>(distribution);
+                        counts.record(mutation);
+                    }
+                }
+
+                // Changes to which genes are on can leave a dead node,
+                // so they're undone if the network isn't valid.
+                Mutation::ToggleEnabled => {
+                    if genome.genes.is_empty() {
+                        continue;
+                    }
+                    let at = self.rng.gen_range(0..genome.genes.len());
+                    let gene = &genome.genes[at];
+                    if !gene.enabled && genome.reaches(gene.out_node, gene.in_node) {
+                        continue;
+                    }
+                    genome.toggle(at);
+                    if Network::new(genome).is_err() {
+                        genome.toggle(at);
+                        continue;
+                    }
+                    counts.record(mutation);
+                }
+
+                Mutation::DeleteGene => {
+                    if genome.genes.is_empty() {
+                        continue;
+                    }
+                    let before = genome.clone();
+                    genome
+                        .genes
+                        .remove(self.rng.gen_range(0..genome.genes.len()));
+                    genome.prune_nodes();
+                    if Network::new(genome).is_err() {
+                        *genome = before;
+                        continue;
+                    }
+                    counts.record(mutation);
+                }
+
+                Mutation::DeleteNode => {
+                    if genome.hidden_nodes.is_empty() {
+                        continue;
+                    }
+                    let before = genome.clone();
+                    let at = self.rng.gen_range(0..genome.hidden_nodes.len());
+                    let node = genome.hidden_nodes.remove(at);
+                    genome
+                        .genes
+                        .retain(|gene| gene.in_node != node && gene.out_node != node);
+                    genome.prune_nodes();
+                    if Network::new(genome).is_err() {
+                        *genome = before;
+                        continue;
                     }
+                    counts.record(mutation);
                 }
 
+                // only enabled genes are split, the new node would turn a disabled one back on
                 Mutation::AddNode => {
-                    if let Some(gene) = genome.genes.choose_mut(&mut rand::thread_rng()) {
-                        let gene = Rc::make_mut(gene);
-                        gene.enabled = false;
-                        let in_node = gene.in_node;
-                        let out_node = gene.out_node;
-                        let new_node = NodeId(self.node_count);
-                        let weight = gene.weight;
-
-                        let gene_id = GeneId(self.genes.count());
-                        genome.genes.push(Rc::new(Gene {
-                            weight,
+                    let enabled: Vec<&Arc<Gene>> =
+                        genome.genes.iter().filter(|gene| gene.enabled).collect();
+                    let Some(split) = enabled.choose(&mut self.rng).map(|gene| Arc::clone(gene))
+                    else {
+                        continue;
+                    };
+                    // the same split elsewhere this generation makes the same node and genes
+                    let new_node = *innovations
+                        .splits
+                        .entry(split.id)
+                        .or_insert(NodeId(self.node_count));
+                    if genome.hidden_nodes.contains(&new_node) {
+                        continue;
+                    }
+                    if new_node.0 == self.node_count {
+                        self.node_count += 1;
+                    }
+                    let position = genome.genes.iter().position(|g| g.id == split.id);
+                    let gene = Arc::make_mut(&mut genome.genes[position.expect("chosen gene")]);
+                    gene.enabled = false;
+
+                    for (in_node, out_node) in
+                        [(split.in_node, new_node), (new_node, split.out_node)]
+                    {
+                        let id = self.genes.insert(in_node, out_node);
+                        genome.insert_gene(Gene {
+                            weight: split.weight,
                             enabled: true,
-                            id: gene_id,
+                            id,
                             in_node,
-                            out_node: new_node,
-                        }));
-                        self.genes.insert(in_node, new_node);
-
-                        let gene_id = GeneId(self.genes.count());
-                        genome.genes.push(Rc::new(Gene {
-                            weight,
-                            enabled: true,
-                            id: gene_id,
-                            in_node: new_node,
                             out_node,
-                        }));
-                        self.genes.insert(new_node, out_node);
-
-                        genome.hidden_nodes.push(new_node);
-                        self.node_count += 1;
+                        });
                     }
+                    let at = genome.hidden_nodes.partition_point(|node| *node < new_node);
+                    genome.hidden_nodes.insert(at, new_node);
+                    counts.record(mutation);
                 }
 
                 Mutation::AddGene => {
                     // randomly select an input or hidden node.
                     // randomly select an output or hidden node.
-                    // create the network and check for cycles.
-                    // If the connection exists, fallback to tweaking the weight or abort
-                    // If the connection creates a cycle, and its between two hidden nodes, then
-                    // try reversing the direction of the connection.
-                    // Otherwise just skip adding the gene.
-                    //
+                    // Skip if the genome already has the connection either way,
+                    // or if it would create a cycle.
                     let node_counts = genome.node_counts();
-                    let h = node_counts.hidden_nodes();
+                    let hidden = &genome.hidden_nodes;
                     let i = node_counts.in_nodes;
-                    let chosen_input = rand::thread_rng().gen_range(0..h + i);
-                    let input_index = if chosen_input < i {
-                        chosen_input
+                    let o = node_counts.out_nodes;
+                    let chosen_input = self.rng.gen_range(0..i + hidden.len());
+                    let in_node = if chosen_input < i {
+                        NodeId(chosen_input)
                     } else {
-                        chosen_input + node_counts.out_nodes
+                        hidden[chosen_input - i]
+                    };
+                    let chosen_output = self.rng.gen_range(0..o + hidden.len());
+                    let out_node = if chosen_output < o {
+                        NodeId(i + chosen_output)
+                    } else {
+                        hidden[chosen_output - o]
                     };
 
-                    let output_index = rand::thread_rng().gen_range(i..node_counts.total_nodes);
-
-                    let in_node = NodeId(input_index);
-                    let out_node = NodeId(output_index);
-
-                    if in_node == out_node || self.genes.contains(in_node, out_node) {
+                    if in_node == out_node
+                        || genome.connects(in_node, out_node)
+                        || genome.reaches(out_node, in_node)
+                    {
                         continue;
                     }
-                    let gene_id = GeneId(self.genes.count());
-                    genome.genes.push(Rc::new(Gene {
+                    // the same connection anywhere else, in any generation, has the same id
+                    let id = self.genes.id(in_node, out_node);
+                    let at = genome.insert_gene(Gene {
                         weight: 0.5,
                         enabled: true,
-                        id: gene_id,
+                        id,
                         in_node,
                         out_node,
-                    }));
+                    });
 
+                    // a hidden node whose genes out are all disabled would be left dead
                     if let Err(error) = Network::new(genome) {
-                        log::info!("in: {in_node:?}, out: {out_node:?}");
-                        log::error!("Error during AddGene mutation:\n{}", error);
-                        genome.genes.remove(genome.genes.len() - 1);
+                        log::debug!("Skipping AddGene {in_node:?} -> {out_node:?}: {}", error);
+                        genome.genes.remove(at);
                     } else {
                         self.genes.insert(in_node, out_node);
+                        counts.record(mutation);
                     }
                 }
             }
         }
+        counts
     }
 }
 
-#[derive(Clone, Debug)]
+// Splits the population between species in proportion to their adjusted fitness, summing exactly to
+// the population. Each species first gets the minimum size, best species first, while there's room.
+// The rest is shared by largest remainder, so rounding doesn't grow or shrink the population.
+// Negative fitness, like a game score, is shifted up so the worst species shares nothing.
+pub fn allocate_offspring(fitness: &[f64], population: usize, min_size: usize) -> Vec<usize> {
+    let mut offspring = vec![0; fitness.len()];
+    let min_size = min_size.min(population);
+    let mut order: Vec<usize> = (0..fitness.len()).collect();
+    order.sort_by(|a, b| fitness[*b].total_cmp(&fitness[*a]).then(a.cmp(b)));
+    let kept = match min_size {
+        0 => order.len(),
+        _ => order.len().min(population / min_size),
+    };
+    order.truncate(kept);
+    if order.is_empty() {
+        return offspring;
+    }
+
+    let remaining = population - kept * min_size;
+    let floor = order.iter().map(|j| fitness[*j]).fold(0.0, f64::min);
+    let total: f64 = order.iter().map(|j| fitness[*j] - floor).sum();
+    let mut remainders = Vec::with_capacity(order.len());
+    for j in order.iter().copied() {
+        let share = if total > 0.0 {
+            (fitness[j] - floor) / total
+        } else {
+            1.0 / order.len() as f64
+        };
+        let quota = share * remaining as f64;
+        offspring[j] = min_size + quota.floor() as usize;
+        remainders.push((quota - quota.floor(), j));
+    }
+    let allocated: usize = offspring.iter().sum();
+    remainders.sort_by(|a, b| b.0.total_cmp(&a.0).then(a.1.cmp(&b.1)));
+    for (_, j) in remainders.into_iter().take(population - allocated) {
+        offspring[j] += 1;
+    }
+    offspring
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct ScoredGenome {
     pub fitness: Fitness,
-    pub genome: Rc<Genome>,
+    pub genome: Arc<Genome>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct Checkpoint {
+    version: u32,
+    generation: usize,
+    champion: ScoredGenome,
+    genomes: Vec<Arc<Genome>>,
+    genes: Genes,
+    node_count: usize,
+    species: Vec<Species>,
+    next_species_id: usize,
+    // where the threshold control had got to
+    threshold: f64,
+    rng: ChaCha12Rng,
+    held_out: Vec<u64>,
+}
+
+impl Checkpoint {
+    // bumped whenever the format changes
+    const VERSION: u32 = 3;
 }
 
+#[derive(Clone, Serialize, Deserialize)]
 pub struct Species {
-    pub genomes: Vec<Rc<Genome>>,
+    pub id: usize,
+    pub representative: Arc<Genome>,
+    // members this generation
+    #[serde(skip)]
+    pub genomes: Vec<Arc<Genome>>,
+    // generations it's been around
+    pub age: usize,
+    pub best_fitness: f64,
+    // generations since best_fitness improved
+    pub stagnant: usize,
+}
+
+impl Species {
+    fn new(id: usize, representative: Arc<Genome>) -> Self {
+        Self {
+            id,
+            genomes: vec![representative.clone()],
+            representative,
+            age: 0,
+            best_fitness: f64::NEG_INFINITY,
+            stagnant: 0,
+        }
+    }
+
+    // after the species' members have been scored
+    fn improve(&mut self, best: f64) {
+        self.age += 1;
+        if best > self.best_fitness {
+            self.best_fitness = best;
+            self.stagnant = 0;
+        } else {
+            self.stagnant += 1;
+        }
+    }
 }
 
 pub struct Speciation {
@@ -540,9 +1104,35 @@ pub struct Speciation {
     c3: f64, // weight
     // compatibility threshold
     ct: f64,
+    pub control: Option<ThresholdControl>,
+}
+
+// Nudges the compatibility threshold every generation, towards a number of species.
+pub struct ThresholdControl {
+    pub target: usize,
+    pub step: f64,
+    pub min: f64,
+    pub max: f64,
 }
 
 impl Speciation {
+    pub fn threshold(&self) -> f64 {
+        self.ct
+    }
+
+    // fewer species than the target lowers the threshold, so genomes split up more easily
+    fn adjust(&mut self, species: usize) {
+        let Some(control) = &self.control else {
+            return;
+        };
+        if species < control.target {
+            self.ct -= control.step;
+        } else if species > control.target {
+            self.ct += control.step;
+        }
+        self.ct = self.ct.clamp(control.min, control.max);
+    }
+
     fn compatible(&self, a: &Genome, b: &Genome) -> bool {
         // calculate speciation distance
         // counts
@@ -590,7 +1180,7 @@ impl Speciation {
             }
         }
 
-        let mut speciation_distance: f64 = 0.0.into();
+        let mut speciation_distance: f64 = 0.0;
         let num_genes = std::cmp::max(a.genes.len(), b.genes.len());
         if num_genes > 0 {
             speciation_distance +=
@@ -604,10 +1194,12 @@ impl Speciation {
     }
 }
 
-#[derive(Default, Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[derive(
+    Default, Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize,
+)]
 pub struct NodeId(pub usize);
 
-#[derive(PartialEq, Eq, PartialOrd, Ord, Debug, Clone, Copy, Hash)]
+#[derive(PartialEq, Eq, PartialOrd, Ord, Debug, Clone, Copy, Hash, Serialize, Deserialize)]
 pub struct GeneId(pub usize);
 
 #[derive(Default, Debug, PartialEq, Eq, Clone, Copy)]
@@ -619,9 +1211,9 @@ pub enum NodeType {
 }
 
 /* === Genome description === */
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct Genome {
-    pub genes: Vec<Rc<Gene>>,
+    pub genes: Vec<Arc<Gene>>,
     pub hidden_nodes: Vec<NodeId>,
     pub in_nodes: usize,
     pub out_nodes: usize,
@@ -636,6 +1228,62 @@ impl Genome {
             hidden_nodes: vec![],
         }
     }
+    // genes are kept in order of id, so they line up when merging and comparing genomes
+    fn insert_gene(&mut self, gene: Gene) -> usize {
+        let at = self.genes.partition_point(|g| g.id < gene.id);
+        self.genes.insert(at, Arc::new(gene));
+        at
+    }
+
+    fn toggle(&mut self, at: usize) {
+        let gene = Arc::make_mut(&mut self.genes[at]);
+        gene.enabled = !gene.enabled;
+    }
+
+    // hidden nodes left without any genes
+    fn prune_nodes(&mut self) {
+        let genes = &self.genes;
+        self.hidden_nodes.retain(|node| {
+            genes
+                .iter()
+                .any(|gene| gene.in_node == *node || gene.out_node == *node)
+        });
+    }
+
+    // connected either way
+    fn connects(&self, a: NodeId, b: NodeId) -> bool {
+        self.genes
+            .iter()
+            .any(|g| (g.in_node, g.out_node) == (a, b) || (g.in_node, g.out_node) == (b, a))
+    }
+
+    // Whether enabled genes lead from one node to the other,
+    // so a gene back the other way would make a cycle.
+    fn reaches(&self, from: NodeId, to: NodeId) -> bool {
+        let mut stack = vec![from];
+        let mut seen = HashSet::new();
+        while let Some(node) = stack.pop() {
+            if node == to {
+                return true;
+            }
+            if seen.insert(node) {
+                stack.extend(
+                    self.genes
+                        .iter()
+                        .filter(|gene| gene.enabled && gene.in_node == node)
+                        .map(|gene| gene.out_node),
+                );
+            }
+        }
+        false
+    }
+
+    fn has_cycle(&self) -> bool {
+        self.genes
+            .iter()
+            .any(|gene| gene.enabled && self.reaches(gene.out_node, gene.in_node))
+    }
+
     pub fn node_counts(&self) -> NodeCounts {
         NodeCounts {
             in_nodes: self.in_nodes,
@@ -643,9 +1291,47 @@ impl Genome {
             total_nodes: self.in_nodes + self.out_nodes + self.hidden_nodes.len(),
         }
     }
+
+    // The encoding names the domain's inputs and outputs, so whoever loads it knows how to play it.
+    pub fn save(&self, path: impl AsRef<Path>, encoding: &str) -> Result<()> {
+        let file = GenomeFile {
+            version: GenomeFile::VERSION,
+            encoding: encoding.to_string(),
+            genome: self.clone(),
+        };
+        fs::write(path, serde_json::to_string_pretty(&file)?)?;
+        Ok(())
+    }
+
+    // The genome, and the encoding it was trained with.
+    pub fn load(path: impl AsRef<Path>) -> Result<(Genome, String)> {
+        let path = path.as_ref();
+        let file: GenomeFile = serde_json::from_slice(&fs::read(path)?)?;
+        if file.version != GenomeFile::VERSION {
+            bail!(
+                "Genome version {} in {} can't be loaded, expected {}",
+                file.version,
+                path.display(),
+                GenomeFile::VERSION
+            );
+        }
+        Ok((file.genome, file.encoding))
+    }
 }
 
-#[derive(Clone, Debug)]
+#[derive(Serialize, Deserialize)]
+struct GenomeFile {
+    version: u32,
+    encoding: String,
+    genome: Genome,
+}
+
+impl GenomeFile {
+    // bumped whenever the format changes
+    const VERSION: u32 = 1;
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct Gene {
     pub id: GeneId,
     pub in_node: NodeId,
@@ -653,3 +1339,359 @@ pub struct Gene {
     pub weight: f64,
     pub enabled: bool,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sums(network: &mut Network, seeds: &[u64]) -> f64 {
+        seeds
+            .iter()
+            .map(|seed| network.run(&[(seed % 7) as f64, (seed % 3) as f64])[0])
+            .sum()
+    }
+
+    fn sums_config(parameters: Parameters) -> Config {
+        Config {
+            domain: DomainConfig {
+                input_layer_size: 2,
+                output_layer_size: 1,
+                encoding: "sums".to_string(),
+                fitness: Box::new(sums),
+            },
+            parameters,
+        }
+    }
+
+    fn run(workers: usize) -> Population {
+        let mut population = Population::new(sums_config(Parameters {
+            population: 20,
+            games: 5,
+            seed: Some(3),
+            workers,
+            ..Default::default()
+        }));
+        for _ in 0..10 {
+            population.advance_gen();
+        }
+        population
+    }
+
+    #[test]
+    fn test_workers_do_not_change_results() {
+        let weights = |population: &Population| -> Vec<Vec<(GeneId, f64)>> {
+            population
+                .genomes
+                .iter()
+                .map(|genome| genome.genes.iter().map(|g| (g.id, g.weight)).collect())
+                .collect()
+        };
+        let single = run(1);
+        let parallel = run(4);
+        assert_eq!(weights(&single), weights(&parallel));
+        assert!(single.gene_count() > 0);
+        assert_eq!(
+            single.champion.fitness.held_out,
+            parallel.champion.fitness.held_out
+        );
+    }
+
+    #[test]
+    fn test_offspring_sum_to_the_population() {
+        assert_eq!(allocate_offspring(&[1.0, 1.0, 1.0], 10, 0), vec![4, 3, 3]);
+        assert_eq!(allocate_offspring(&[3.0, 1.0], 10, 2), vec![7, 3]);
+        // no room for every species at the minimum size, the worst ones die out
+        assert_eq!(allocate_offspring(&[1.0, 2.0, 3.0], 5, 2), vec![0, 2, 3]);
+        assert_eq!(allocate_offspring(&[0.0, 0.0], 5, 0), vec![3, 2]);
+        assert_eq!(allocate_offspring(&[-4.0, -2.0, 2.0], 10, 0), vec![0, 3, 7]);
+        for population in 1..30 {
+            let offspring = allocate_offspring(&[0.3, 2.5, 0.01, 1.7], population, 2);
+            assert_eq!(offspring.iter().sum::<usize>(), population);
+        }
+    }
+
+    #[test]
+    fn test_population_size_is_stable_and_keeps_its_best() {
+        let mut population = run(1);
+        let mut new_champions = 0;
+        for _ in 0..10 {
+            let champion = population.champion.genome.clone();
+            population.advance_gen();
+            assert_eq!(population.genomes.len(), 20);
+
+            // a new champion was the generation's best, so it's carried over unchanged
+            let genome = &population.champion.genome;
+            if !Arc::ptr_eq(genome, &champion) {
+                new_champions += 1;
+                assert!(population.genomes.iter().any(|g| Arc::ptr_eq(g, genome)));
+            }
+        }
+        assert!(new_champions > 0);
+    }
+
+    #[test]
+    fn test_species_persist_until_they_stagnate() {
+        let mut population = Population::new(Config {
+            domain: DomainConfig {
+                input_layer_size: 2,
+                output_layer_size: 1,
+                encoding: "flat".to_string(),
+                fitness: Box::new(|_, _| 1.0),
+            },
+            parameters: Parameters {
+                population: 20,
+                games: 1,
+                seed: Some(5),
+                workers: 1,
+                stagnation: 3,
+                protected_species: 1,
+                ..Default::default()
+            },
+        });
+        let mut ages = HashMap::new();
+        for _ in 0..10 {
+            let report = population.advance_gen();
+            for species in report.species.iter() {
+                if let Some(age) = ages.insert(species.id, species.age) {
+                    assert_eq!(species.age, age + 1);
+                }
+            }
+            // nothing improves after the first generation, so only the protected one gets old
+            let old = population.species.iter().filter(|s| s.stagnant >= 3);
+            assert!(old.count() <= 1);
+        }
+        assert!(ages.values().any(|age| *age > 3));
+    }
+
+    #[test]
+    fn test_threshold_moves_towards_target_species() {
+        let mut speciation = Parameters::default().speciation;
+        speciation.adjust(1);
+        assert_eq!(speciation.threshold(), 4.0);
+
+        speciation.control = Some(ThresholdControl {
+            target: 3,
+            step: 1.0,
+            min: 1.0,
+            max: 5.0,
+        });
+        speciation.adjust(1);
+        assert_eq!(speciation.threshold(), 3.0);
+        speciation.adjust(3);
+        assert_eq!(speciation.threshold(), 3.0);
+        for _ in 0..5 {
+            speciation.adjust(1);
+        }
+        assert_eq!(speciation.threshold(), 1.0);
+        for _ in 0..5 {
+            speciation.adjust(10);
+        }
+        assert_eq!(speciation.threshold(), 5.0);
+    }
+
+    #[test]
+    fn test_same_mutations_share_innovations() {
+        let config = |mutation| {
+            sums_config(Parameters {
+                population: 10,
+                mutation_rate: 1.0,
+                mutation: MutationWeights::new([(mutation, 1)]),
+                seed: Some(9),
+                ..Default::default()
+            })
+        };
+
+        // every genome connects the inputs to the output, with the same ids for the same pairs
+        let mut population = Population::new(config(Mutation::AddGene));
+        for _ in 0..5 {
+            population.mutate_population();
+        }
+        let mut ids = HashMap::new();
+        for genome in population.genomes.iter() {
+            for gene in genome.genes.iter() {
+                let id = ids.entry((gene.in_node, gene.out_node)).or_insert(gene.id);
+                assert_eq!(*id, gene.id);
+            }
+        }
+        assert_eq!(ids.len(), 2);
+
+        // and every genome splits its only gene into the same node
+        let mut split = (*population.genomes[0]).clone();
+        split.genes.truncate(1);
+        population.config = config(Mutation::AddNode);
+        let split = Arc::new(split);
+        population.genomes = vec![split; 10];
+        population.mutate_population();
+        let first = &population.genomes[0];
+        for genome in population.genomes.iter() {
+            assert_eq!(genome.hidden_nodes, first.hidden_nodes);
+            Network::new(genome).unwrap();
+        }
+        assert_eq!(population.node_count, 4);
+    }
+
+    #[test]
+    fn test_every_mutation_keeps_networks_valid() {
+        let mut population = Population::new(sums_config(Parameters {
+            population: 20,
+            games: 3,
+            seed: Some(11),
+            workers: 1,
+            mutation_rate: 1.0,
+            reenable_rate: 1.0,
+            mutation: MutationWeights::new([
+                (Mutation::AdjustWeight, 1),
+                (Mutation::ReplaceWeight, 1),
+                (Mutation::AddNode, 3),
+                (Mutation::AddGene, 3),
+                (Mutation::ToggleEnabled, 1),
+                (Mutation::DeleteGene, 1),
+                (Mutation::DeleteNode, 1),
+            ]),
+            ..Default::default()
+        }));
+        let mut counts = MutationCounts::default();
+        for _ in 0..50 {
+            let mutations = population.advance_gen().mutations;
+            counts.toggle_enabled += mutations.toggle_enabled;
+            counts.delete_gene += mutations.delete_gene;
+            counts.delete_node += mutations.delete_node;
+            for genome in population.genomes.iter() {
+                assert!(!genome.has_cycle());
+                Network::new(genome).unwrap();
+                assert!(genome.genes.windows(2).all(|w| w[0].id < w[1].id));
+                assert!(genome.hidden_nodes.windows(2).all(|w| w[0] < w[1]));
+            }
+        }
+        assert!(counts.toggle_enabled > 0);
+        assert!(counts.delete_gene > 0);
+        assert!(counts.delete_node > 0);
+    }
+
+    #[test]
+    fn test_report_covers_the_generation() {
+        let mut population = run(1);
+        let genomes = population.genomes.len();
+        let report = population.advance_gen();
+
+        assert_eq!(report.generation, 10);
+        assert_eq!(report.threshold, 4.0);
+        let sizes: usize = report.species.iter().map(|s| s.size).sum();
+        assert_eq!(sizes, genomes);
+        assert!(report.actual.min <= report.actual.mean);
+        assert!(report.actual.mean <= report.actual.max);
+        assert!(report.adjusted.max <= report.actual.max);
+        // at most one per genome picked to mutate
+        let mutations = &report.mutations;
+        assert!(mutations.adjust_weight + mutations.add_node + mutations.add_gene <= 6);
+    }
+
+    #[test]
+    fn test_resumes_from_checkpoint() {
+        let weights = |population: &Population| -> Vec<Vec<(GeneId, f64)>> {
+            population
+                .genomes
+                .iter()
+                .map(|genome| genome.genes.iter().map(|g| (g.id, g.weight)).collect())
+                .collect()
+        };
+        let path = std::env::temp_dir().join(format!("checkpoint-{}.json", std::process::id()));
+        let mut population = run(2);
+        population.save(&path).unwrap();
+        let config = sums_config(Parameters {
+            population: 20,
+            games: 5,
+            ..Default::default()
+        });
+        let mut resumed = Population::load(&path, config).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        for _ in 0..5 {
+            population.advance_gen();
+            resumed.advance_gen();
+        }
+        assert_eq!(resumed.generation, population.generation);
+        assert_eq!(weights(&resumed), weights(&population));
+    }
+
+    #[test]
+    fn test_checkpoint_weights_round_trip_exactly() {
+        let path = std::env::temp_dir().join(format!("weights-{}.json", std::process::id()));
+        let config = || {
+            sums_config(Parameters {
+                population: 2,
+                ..Default::default()
+            })
+        };
+        let gene = |id, in_node, weight| {
+            Arc::new(Gene {
+                id: GeneId(id),
+                in_node: NodeId(in_node),
+                out_node: NodeId(2),
+                weight,
+                enabled: true,
+            })
+        };
+        let mut population = Population::new(config());
+        population.genomes[0] = Arc::new(Genome {
+            genes: vec![
+                gene(0, 0, 0.1 + 0.2),
+                gene(1, 1, 1.0 / 3.0),
+                // these two came back a bit off without serde_json's float_roundtrip
+                gene(2, 0, 0.10415975566504469),
+                gene(3, 1, -1.8189563980895058),
+            ],
+            hidden_nodes: vec![],
+            in_nodes: 2,
+            out_nodes: 1,
+        });
+        population.save(&path).unwrap();
+        let resumed = Population::load(&path, config()).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        let weights: Vec<f64> = resumed.genomes[0].genes.iter().map(|g| g.weight).collect();
+        let expected = vec![
+            0.1 + 0.2,
+            1.0 / 3.0,
+            0.10415975566504469,
+            -1.8189563980895058,
+        ];
+        assert_eq!(weights, expected);
+    }
+
+    #[test]
+    fn test_lucky_generation_does_not_replace_champion() {
+        let mut population = Population::new(sums_config(Parameters {
+            seed: Some(11),
+            ..Default::default()
+        }));
+        let scored = |weight, actual| ScoredGenome {
+            fitness: Fitness {
+                actual,
+                adjusted: actual,
+                held_out: None,
+            },
+            genome: Arc::new(Genome {
+                genes: vec![Arc::new(Gene {
+                    id: GeneId(0),
+                    in_node: NodeId(0),
+                    out_node: NodeId(2),
+                    weight,
+                    enabled: true,
+                })],
+                hidden_nodes: vec![],
+                in_nodes: 2,
+                out_nodes: 1,
+            }),
+        };
+        population.challenge(scored(1.0, 0.0));
+        let champion = population.champion.genome.clone();
+
+        // its generation scored it far higher, but the held out seeds find it worse
+        population.challenge(scored(-1.0, 100.0));
+        assert!(Arc::ptr_eq(&population.champion.genome, &champion));
+
+        population.challenge(scored(2.0, 0.0));
+        assert!(!Arc::ptr_eq(&population.champion.genome, &champion));
+    }
+}
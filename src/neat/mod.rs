@@ -1,2 +1,3 @@
 pub mod genome;
 pub mod network;
+pub mod report;
@@ -6,7 +6,6 @@ use std::fs::{self};
 use std::io::Write;
 use std::process::Command;
 use std::rc::Rc;
-use std::usize;
 
 #[derive(Default, Debug, PartialEq, Eq, Clone)]
 pub enum Activation {
@@ -47,25 +46,25 @@ impl Network {
             .create(true)
             .open(format!("graphviz/champion-{}.dot", gen))?;
         let indent = "";
-        write!(&mut file, "strict digraph {{\n")?;
-        write!(&mut file, "{indent: <2}subgraph {{\n")?;
+        writeln!(&mut file, "strict digraph {{")?;
+        writeln!(&mut file, "{indent: <2}subgraph {{")?;
         write!(&mut file, "{indent: <4}rank=min;\n{indent: <4}")?;
         for node_index in self.node_counts.input_range() {
             write!(&mut file, "{}; ", node_index)?;
         }
-        write!(&mut file, "\n{indent: <2}}}\n")?;
+        writeln!(&mut file, "\n{indent: <2}}}")?;
 
-        write!(&mut file, "{indent: <2}subgraph {{\n")?;
+        writeln!(&mut file, "{indent: <2}subgraph {{")?;
         write!(&mut file, "{indent: <4}rank=max;\n{indent: <4}")?;
         for node_index in self.node_counts.output_range() {
             write!(&mut file, "{}; ", node_index)?;
         }
-        write!(&mut file, "\n{indent: <2}}}\n")?;
+        writeln!(&mut file, "\n{indent: <2}}}")?;
 
         for edge in self.sorted_edges.iter() {
-            write!(
+            writeln!(
                 &mut file,
-                "{indent: <2}{} -> {} [label=\"{}@{:.2}\"]\n",
+                "{indent: <2}{} -> {} [label=\"{}@{:.2}\"]",
                 edge.in_node.0, edge.out_node.0, edge.id.0, edge.weight,
             )?;
         }
@@ -118,23 +117,25 @@ impl Network {
 
         let mut incoming = vec![Vec::new(); node_counts.total_nodes];
         let mut outgoing = vec![Vec::new(); node_counts.total_nodes];
+        let mut edge_count = 0;
         for edge in edges {
             incoming[edge.out_node.0].push(edge.clone());
             outgoing[edge.in_node.0].push(edge.clone());
-
-            if node_counts.input_range().contains(&edge.in_node.0) {
-                edges_to_sort.push(edge.clone());
+            edge_count += 1;
+        }
+        // edges out of nodes with nothing coming in, inputs or hidden nodes cut off from them
+        for (node, edges) in outgoing.iter().enumerate() {
+            if incoming[node].is_empty() {
+                edges_to_sort.extend_from_slice(edges);
             }
         }
 
-        if cfg!(debug_assertions) {
-            let network_dead_node = node_counts
-                .hidden_range()
-                .find(|i| !incoming[*i].is_empty() && outgoing[*i].is_empty());
-
-            if let Some(node_id) = network_dead_node {
-                bail!("Dead node: {node_id}");
-            }
+        // checked in release builds too, the mutations rely on it to reject a genome
+        let network_dead_node = node_counts
+            .hidden_range()
+            .find(|i| !incoming[*i].is_empty() && outgoing[*i].is_empty());
+        if let Some(node_id) = network_dead_node {
+            bail!("Dead node: {node_id}");
         }
 
         let mut visited: HashSet<GeneId> = HashSet::with_capacity(genome.genes.len());
@@ -152,6 +153,10 @@ impl Network {
             }
             sorted_edges.push(edge);
         }
+        // edges in a cycle never have all of their inputs sorted
+        if sorted_edges.len() != edge_count {
+            bail!("Neural net contains a cycle")
+        }
 
         let mut node_values = vec![0.0; node_counts.total_nodes];
         for i in node_counts.output_range() {
@@ -215,7 +220,7 @@ impl NeuralInterface for Network {
 }
 
 pub fn sigmoid(num: f64) -> f64 {
-    1.0 / (1.0 + -num.exp())
+    1.0 / (1.0 + (-num).exp())
 }
 
 #[macro_export]
@@ -239,9 +244,56 @@ macro_rules! assert_approx_eq {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use std::sync::Arc;
 
     #[test]
     fn test_sigmoid() {
         assert_approx_eq!(sigmoid(1.0), 0.7310585, 1e-7);
     }
+
+    #[test]
+    fn test_hidden_cycle_is_rejected() {
+        let gene = |id, in_node, out_node| {
+            Arc::new(Gene {
+                id: GeneId(id),
+                in_node: NodeId(in_node),
+                out_node: NodeId(out_node),
+                weight: 1.0,
+                enabled: true,
+            })
+        };
+        // 0 -> 2 -> 3 -> 2, and 3 -> 1
+        let genome = Genome {
+            genes: vec![gene(0, 0, 2), gene(1, 2, 3), gene(2, 3, 2), gene(3, 3, 1)],
+            hidden_nodes: vec![NodeId(2), NodeId(3)],
+            in_nodes: 1,
+            out_nodes: 1,
+        };
+        assert!(Network::new(&genome).is_err());
+    }
+
+    #[test]
+    fn test_dead_node_is_rejected() {
+        let gene = |id, in_node, out_node, enabled| {
+            Arc::new(Gene {
+                id: GeneId(id),
+                in_node: NodeId(in_node),
+                out_node: NodeId(out_node),
+                weight: 1.0,
+                enabled,
+            })
+        };
+        // 0 -> 2, but the only way out of 2 is off
+        let genome = Genome {
+            genes: vec![
+                gene(0, 0, 2, true),
+                gene(1, 2, 1, false),
+                gene(2, 0, 1, true),
+            ],
+            hidden_nodes: vec![NodeId(2)],
+            in_nodes: 1,
+            out_nodes: 1,
+        };
+        assert!(Network::new(&genome).is_err());
+    }
 }
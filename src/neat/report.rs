@@ -0,0 +1,154 @@
+use super::genome::Mutation;
+use anyhow::Result;
+use serde::Serialize;
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::Path;
+
+// What happened in a generation, for plotting how a run is going.
+#[derive(Clone, Debug, Serialize)]
+pub struct GenerationReport {
+    // the generation that was scored
+    pub generation: usize,
+    pub actual: Stats,
+    pub adjusted: Stats,
+    pub species: Vec<SpeciesReport>,
+    // compatibility threshold the species were classified with
+    pub threshold: f64,
+    // genes and hidden nodes per genome, before reproducing
+    pub mean_genes: f64,
+    pub mean_hidden_nodes: f64,
+    pub mutations: MutationCounts,
+    pub timings: Timings,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize)]
+pub struct Stats {
+    pub min: f64,
+    pub mean: f64,
+    pub max: f64,
+    pub std_dev: f64,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct SpeciesReport {
+    pub id: usize,
+    pub age: usize,
+    // generations without improving
+    pub stagnant: usize,
+    pub size: usize,
+    pub offspring: usize,
+}
+
+// Mutations that changed a genome, ones with nothing to act on aren't counted.
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct MutationCounts {
+    pub adjust_weight: usize,
+    pub replace_weight: usize,
+    pub add_node: usize,
+    pub add_gene: usize,
+    pub toggle_enabled: usize,
+    pub delete_gene: usize,
+    pub delete_node: usize,
+}
+
+// in seconds
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct Timings {
+    pub speciate: f64,
+    pub evaluate: f64,
+    // scoring the generation's best on the held out seeds
+    pub challenge: f64,
+    pub reproduce: f64,
+    pub mutate: f64,
+    pub total: f64,
+}
+
+impl Stats {
+    pub fn new(values: &[f64]) -> Self {
+        if values.is_empty() {
+            return Self::default();
+        }
+        let n = values.len() as f64;
+        let mean = values.iter().sum::<f64>() / n;
+        let variance = values.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / n;
+        Self {
+            min: values.iter().copied().fold(f64::INFINITY, f64::min),
+            mean,
+            max: values.iter().copied().fold(f64::NEG_INFINITY, f64::max),
+            std_dev: variance.sqrt(),
+        }
+    }
+}
+
+impl MutationCounts {
+    pub fn record(&mut self, mutation: Mutation) {
+        match mutation {
+            Mutation::AdjustWeight => self.adjust_weight += 1,
+            Mutation::ReplaceWeight => self.replace_weight += 1,
+            Mutation::AddNode => self.add_node += 1,
+            Mutation::AddGene => self.add_gene += 1,
+            Mutation::ToggleEnabled => self.toggle_enabled += 1,
+            Mutation::DeleteGene => self.delete_gene += 1,
+            Mutation::DeleteNode => self.delete_node += 1,
+        }
+    }
+}
+
+impl GenerationReport {
+    // One line of json per generation.
+    pub fn write_jsonl(&self, path: impl AsRef<Path>) -> Result<()> {
+        let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+        writeln!(file, "{}", serde_json::to_string(self)?)?;
+        Ok(())
+    }
+
+    // Appends a row, species are summarized since their number changes every generation.
+    pub fn write_csv(&self, path: impl AsRef<Path>) -> Result<()> {
+        let path = path.as_ref();
+        let new = !path.exists();
+        let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+        if new {
+            writeln!(
+                file,
+                "generation,\
+                actual_min,actual_mean,actual_max,actual_std_dev,\
+                adjusted_min,adjusted_mean,adjusted_max,adjusted_std_dev,\
+                species,largest_species,threshold,mean_genes,mean_hidden_nodes,\
+                adjust_weight,replace_weight,add_node,add_gene,\
+                toggle_enabled,delete_gene,delete_node,\
+                evaluate_secs,total_secs"
+            )?;
+        }
+        let largest = self.species.iter().map(|s| s.size).max().unwrap_or(0);
+        let (actual, adjusted) = (&self.actual, &self.adjusted);
+        writeln!(
+            file,
+            "{},{},{},{},{},{},{},{},{},{},{},{:.3},{:.2},{:.2},{},{},{},{},{},{},{},{:.3},{:.3}",
+            self.generation,
+            actual.min,
+            actual.mean,
+            actual.max,
+            actual.std_dev,
+            adjusted.min,
+            adjusted.mean,
+            adjusted.max,
+            adjusted.std_dev,
+            self.species.len(),
+            largest,
+            self.threshold,
+            self.mean_genes,
+            self.mean_hidden_nodes,
+            self.mutations.adjust_weight,
+            self.mutations.replace_weight,
+            self.mutations.add_node,
+            self.mutations.add_gene,
+            self.mutations.toggle_enabled,
+            self.mutations.delete_gene,
+            self.mutations.delete_node,
+            self.timings.evaluate,
+            self.timings.total,
+        )?;
+        Ok(())
+    }
+}
@@ -0,0 +1,295 @@
+use crate::rri::{GameState, Piece, Tile, EXITS};
+use crate::scoring::Board;
+use rand::seq::SliceRandom;
+
+// Goals shared by every board in a match.
+// The first boards to complete one earn the full bonus, later boards earn the reduced bonus.
+pub trait Objective {
+    fn name(&self) -> &'static str;
+    fn complete(&self, state: &GameState) -> bool;
+
+    fn full_bonus(&self) -> isize {
+        6
+    }
+
+    fn reduced_bonus(&self) -> isize {
+        3
+    }
+}
+
+#[derive(Clone, Copy)]
+pub struct ObjectiveCard {
+    pub objective: &'static (dyn Objective + Sync),
+    // completed in an earlier round, by this board or any other.
+    pub claimed: bool,
+    // the bonus earned by this board.
+    pub bonus: Option<isize>,
+}
+
+impl ObjectiveCard {
+    pub fn new(objective: &'static (dyn Objective + Sync)) -> Self {
+        Self {
+            objective,
+            claimed: false,
+            bonus: None,
+        }
+    }
+}
+
+pub static CATALOG: [&(dyn Objective + Sync); 8] = [
+    &CornerExits,
+    &EveryEdge { piece: Piece::Rail },
+    &EveryEdge { piece: Piece::Road },
+    &FullCenter,
+    &LongRoute {
+        piece: Piece::Rail,
+        length: 10,
+    },
+    &LongRoute {
+        piece: Piece::Road,
+        length: 10,
+    },
+    &ExitNetwork { exits: 6 },
+    &Stations { count: 3 },
+];
+
+// A random selection from the catalog, for a single game.
+pub fn deal(count: usize) -> Vec<ObjectiveCard> {
+    CATALOG
+        .choose_multiple(&mut rand::thread_rng(), count)
+        .map(|objective| ObjectiveCard::new(*objective))
+        .collect()
+}
+
+pub fn find(name: &str) -> Option<&'static (dyn Objective + Sync)> {
+    CATALOG
+        .iter()
+        .find(|objective| objective.name() == name)
+        .copied()
+}
+
+impl GameState {
+    // Awards the bonus for each objective this board completed for the first time.
+    pub fn complete_objectives(&mut self) {
+        for i in 0..self.objectives.len() {
+            let card = self.objectives[i];
+            if card.bonus.is_some() || !card.objective.complete(self) {
+                continue;
+            }
+            let bonus = if card.claimed {
+                card.objective.reduced_bonus()
+            } else {
+                card.objective.full_bonus()
+            };
+            self.objectives[i].bonus = Some(bonus);
+            self.objectives[i].claimed = true;
+        }
+    }
+}
+
+// One network connecting an exit next to each of the four corners.
+pub struct CornerExits;
+
+impl Objective for CornerExits {
+    fn name(&self) -> &'static str {
+        "Connect the four corners"
+    }
+
+    fn full_bonus(&self) -> isize {
+        8
+    }
+
+    fn reduced_bonus(&self) -> isize {
+        4
+    }
+
+    fn complete(&self, state: &GameState) -> bool {
+        // the exits in the middle of each edge aren't next to a corner
+        let corner = |index: usize| {
+            let tile = EXITS[index].0.tile;
+            (tile.x != 3 && tile.y != 3).then_some((tile.x < 3, tile.y < 3))
+        };
+        Board::new(state).exit_networks().iter().any(|exits| {
+            [(true, true), (true, false), (false, true), (false, false)]
+                .iter()
+                .all(|c| exits.iter().any(|exit| corner(*exit) == Some(*c)))
+        })
+    }
+}
+
+// Routes with the piece drawn along all four edges of the board.
+pub struct EveryEdge {
+    pub piece: Piece,
+}
+
+impl Objective for EveryEdge {
+    fn name(&self) -> &'static str {
+        match self.piece {
+            Piece::Rail => "Rail touching every edge",
+            Piece::Road => "Road touching every edge",
+        }
+    }
+
+    fn complete(&self, state: &GameState) -> bool {
+        let edges: [fn(&Tile) -> bool; 4] = [
+            |tile| tile.y == 0,
+            |tile| tile.x == 6,
+            |tile| tile.y == 6,
+            |tile| tile.x == 0,
+        ];
+        edges.iter().all(|on_edge| {
+            state.drawn_routes.iter().any(|action| {
+                on_edge(&action.tile)
+                    && [
+                        action.pattern.north,
+                        action.pattern.east,
+                        action.pattern.south,
+                        action.pattern.west,
+                    ]
+                    .contains(&Some(self.piece))
+            })
+        })
+    }
+}
+
+// Routes drawn on all nine center tiles.
+pub struct FullCenter;
+
+impl Objective for FullCenter {
+    fn name(&self) -> &'static str {
+        "Fill the center"
+    }
+
+    fn complete(&self, state: &GameState) -> bool {
+        (2..=4).all(|x| (2..=4).all(|y| state.route_at(Tile { x, y }).is_some()))
+    }
+}
+
+pub struct LongRoute {
+    pub piece: Piece,
+    pub length: isize,
+}
+
+impl Objective for LongRoute {
+    fn name(&self) -> &'static str {
+        match self.piece {
+            Piece::Rail => "Long railway",
+            Piece::Road => "Long highway",
+        }
+    }
+
+    fn complete(&self, state: &GameState) -> bool {
+        Board::new(state).longest(self.piece) >= self.length
+    }
+}
+
+pub struct ExitNetwork {
+    pub exits: usize,
+}
+
+impl Objective for ExitNetwork {
+    fn name(&self) -> &'static str {
+        "Connect exits"
+    }
+
+    fn full_bonus(&self) -> isize {
+        8
+    }
+
+    fn reduced_bonus(&self) -> isize {
+        4
+    }
+
+    fn complete(&self, state: &GameState) -> bool {
+        Board::new(state)
+            .exit_networks()
+            .iter()
+            .any(|exits| exits.len() >= self.exits)
+    }
+}
+
+pub struct Stations {
+    pub count: usize,
+}
+
+impl Objective for Stations {
+    fn name(&self) -> &'static str {
+        "Stations"
+    }
+
+    fn full_bonus(&self) -> isize {
+        4
+    }
+
+    fn reduced_bonus(&self) -> isize {
+        2
+    }
+
+    fn complete(&self, state: &GameState) -> bool {
+        state
+            .drawn_routes
+            .iter()
+            .filter(|action| action.pattern.station)
+            .count()
+            >= self.count
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::routes::DIE_PATTERNS;
+    use crate::rri::{DrawAction, Match};
+
+    static FULL_RAIL: LongRoute = LongRoute {
+        piece: Piece::Rail,
+        length: 7,
+    };
+
+    fn draw_rail(board: &mut GameState, y: u8) {
+        board
+            .apply_route(DrawAction {
+                pattern: &DIE_PATTERNS[8],
+                tile: Tile { x: 3, y },
+            })
+            .unwrap();
+    }
+
+    #[test]
+    fn test_later_boards_earn_reduced_bonus() {
+        let board = || {
+            let mut board = GameState::new();
+            board.objectives = vec![ObjectiveCard::new(&FULL_RAIL)];
+            board
+        };
+        let mut game = Match::new(vec![board(), board(), board()]);
+        for y in 0..6 {
+            for board in game.boards.iter_mut() {
+                draw_rail(board, y);
+            }
+        }
+
+        // the first two boards finish the rail in the same round
+        draw_rail(&mut game.boards[0], 6);
+        draw_rail(&mut game.boards[1], 6);
+        for board in game.boards.iter_mut() {
+            board.complete_objectives();
+        }
+        assert_eq!(game.boards[0].objectives[0].bonus, Some(6));
+        assert_eq!(game.boards[1].objectives[0].bonus, Some(6));
+        assert_eq!(game.boards[2].objectives[0].bonus, None);
+
+        game.share_claims();
+        draw_rail(&mut game.boards[2], 6);
+        game.boards[2].complete_objectives();
+        assert_eq!(game.boards[2].objectives[0].bonus, Some(3));
+    }
+
+    #[test]
+    #[should_panic(expected = "same objectives")]
+    fn test_match_boards_share_objectives() {
+        let mut board = GameState::new();
+        board.objectives = vec![ObjectiveCard::new(&FULL_RAIL)];
+        Match::new(vec![board, GameState::new()]);
+    }
+}
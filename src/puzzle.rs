@@ -0,0 +1,175 @@
+use crate::expansions::{Blocker, Variant};
+use crate::rri::{Dice, DiePattern, Direction, DrawAction, GameState, Tile, EXITS};
+use anyhow::{bail, Result};
+use std::collections::HashMap;
+
+// Builds a game from an arbitrary position, for puzzles and regression tests.
+// Setup routes don't have to connect to the network,
+// but the position as a whole has to be consistent.
+#[derive(Default)]
+pub struct Puzzle {
+    variant: Variant,
+    routes: Vec<DrawAction>,
+    blocked: Vec<(Tile, Blocker)>,
+    dice: Option<Dice>,
+    round: usize,
+}
+
+impl Puzzle {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn variant(mut self, variant: Variant) -> Self {
+        self.variant = variant;
+        self
+    }
+
+    pub fn route(mut self, tile: Tile, pattern: &'static DiePattern) -> Self {
+        self.routes.push(DrawAction { pattern, tile });
+        self
+    }
+
+    pub fn block(mut self, tile: Tile, blocker: Blocker) -> Self {
+        self.blocked.push((tile, blocker));
+        self
+    }
+
+    pub fn roll(mut self, dice: Dice) -> Self {
+        self.dice = Some(dice);
+        self
+    }
+
+    // rounds already played
+    pub fn round(mut self, round: usize) -> Self {
+        self.round = round;
+        self
+    }
+
+    pub fn build(self) -> Result<GameState> {
+        let problems = self.problems();
+        if !problems.is_empty() {
+            bail!("Invalid position:\n{}", problems.join("\n"))
+        }
+
+        let mut state = GameState::with_variant(self.variant);
+        if let Some(dice) = self.dice {
+            state.dice = dice;
+        }
+        state.round = self.round;
+        state.drawn_routes = self.routes;
+        state.blocked_tiles = self.blocked.into_iter().collect();
+        state.open_edges = state.expected_open_edges();
+        Ok(state)
+    }
+
+    // Every reason the position is invalid.
+    fn problems(&self) -> Vec<String> {
+        let mut problems = Vec::new();
+        let mut used: HashMap<Tile, &str> = HashMap::new();
+        let tiles = self
+            .routes
+            .iter()
+            .map(|action| (action.tile, "a route"))
+            .chain(
+                self.blocked
+                    .iter()
+                    .map(|(tile, blocker)| (*tile, blocker.name())),
+            );
+        for (tile, name) in tiles {
+            if tile.x > 6 || tile.y > 6 {
+                problems.push(format!("Tile {tile} is off the board"));
+            } else if let Some(other) = used.insert(tile, name) {
+                problems.push(format!("Tile {tile} is used twice: {other} and {name}"));
+            }
+        }
+
+        let mut routes: HashMap<Tile, &DiePattern> = HashMap::new();
+        for action in self.routes.iter() {
+            routes.entry(action.tile).or_insert(action.pattern);
+        }
+        for (tile, pattern) in routes.iter() {
+            // each shared edge only needs to be checked from one side
+            for direction in [Direction::East, Direction::South] {
+                let Some(neighbor) = tile.neighbor(direction) else {
+                    continue;
+                };
+                let Some(other) = routes.get(&neighbor) else {
+                    continue;
+                };
+                if let (Some(a), Some(b)) =
+                    (pattern.get(direction), other.get(direction.opposite()))
+                {
+                    if a != b {
+                        problems.push(format!(
+                            "{a:?} at {tile} meets {b:?} at {neighbor} on the {direction:?} edge"
+                        ));
+                    }
+                }
+            }
+        }
+        for (edge, exit) in EXITS {
+            let Some(pattern) = routes.get(&edge.tile) else {
+                continue;
+            };
+            if let Some(piece) = pattern.get(edge.direction) {
+                if piece != exit {
+                    problems.push(format!(
+                        "{piece:?} at {} meets the {exit:?} exit on the {:?} edge",
+                        edge.tile, edge.direction
+                    ));
+                }
+            }
+        }
+        problems
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::routes::DIE_PATTERNS;
+    use crate::rri::{Piece, RegularDieFace, SpecialDieFace, TileEdge};
+
+    #[test]
+    fn test_rebuilds_open_edges() {
+        // a straight rail floating in the middle of the board
+        let state = Puzzle::new()
+            .route(Tile::CENTER, &DIE_PATTERNS[8])
+            .roll(Dice {
+                regular: [RegularDieFace::StraightRail; 3],
+                special: SpecialDieFace::Overpass,
+            })
+            .build()
+            .unwrap();
+
+        assert_eq!(state.open_edges.len(), EXITS.len() + 2);
+        assert_eq!(
+            state.open_edges.get(&TileEdge::new(3, 2, Direction::South)),
+            Some(&Piece::Rail)
+        );
+        assert!(state
+            .legal_actions(RegularDieFace::StraightRail.into())
+            .iter()
+            .any(|action| action.tile == Tile { x: 3, y: 4 }));
+    }
+
+    #[test]
+    fn test_reports_every_problem() {
+        let error = Puzzle::new()
+            .route(Tile::CENTER, &DIE_PATTERNS[8])
+            .route(Tile::CENTER, &DIE_PATTERNS[9])
+            // straight road under the rail
+            .route(Tile { x: 3, y: 4 }, &DIE_PATTERNS[18])
+            .build()
+            .err()
+            .expect("position is invalid")
+            .to_string();
+
+        assert!(error.contains("Tile (3, 3) is used twice"), "{error}");
+        assert!(
+            error.contains("Rail at (3, 3) meets Road at (3, 4) on the South edge"),
+            "{error}"
+        );
+    }
+}
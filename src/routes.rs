@@ -3,6 +3,7 @@ use crate::rri::{DieFace, DiePattern, Piece};
 pub const DIE_PATTERNS: [DiePattern; 34] = [
     DiePattern {
         face: DieFace::AngleRail,
+        index: 0,
         north: Some(Piece::Rail),
         east: None,
         south: None,
@@ -11,6 +12,7 @@ pub const DIE_PATTERNS: [DiePattern; 34] = [
     },
     DiePattern {
         face: DieFace::AngleRail,
+        index: 1,
         north: Some(Piece::Rail),
         east: Some(Piece::Rail),
         south: None,
@@ -19,6 +21,7 @@ pub const DIE_PATTERNS: [DiePattern; 34] = [
     },
     DiePattern {
         face: DieFace::AngleRail,
+        index: 2,
         north: None,
         east: Some(Piece::Rail),
         south: Some(Piece::Rail),
@@ -27,6 +30,7 @@ pub const DIE_PATTERNS: [DiePattern; 34] = [
     },
     DiePattern {
         face: DieFace::AngleRail,
+        index: 3,
         north: None,
         east: None,
         south: Some(Piece::Rail),
@@ -35,6 +39,7 @@ pub const DIE_PATTERNS: [DiePattern; 34] = [
     },
     DiePattern {
         face: DieFace::ThreeRail,
+        index: 4,
         north: Some(Piece::Rail),
         east: Some(Piece::Rail),
         south: None,
@@ -43,6 +48,7 @@ pub const DIE_PATTERNS: [DiePattern; 34] = [
     },
     DiePattern {
         face: DieFace::ThreeRail,
+        index: 5,
         north: Some(Piece::Rail),
         east: Some(Piece::Rail),
         south: Some(Piece::Rail),
@@ -51,6 +57,7 @@ pub const DIE_PATTERNS: [DiePattern; 34] = [
     },
     DiePattern {
         face: DieFace::ThreeRail,
+        index: 6,
         north: None,
         east: Some(Piece::Rail),
         south: Some(Piece::Rail),
@@ -59,6 +66,7 @@ pub const DIE_PATTERNS: [DiePattern; 34] = [
     },
     DiePattern {
         face: DieFace::ThreeRail,
+        index: 7,
         north: Some(Piece::Rail),
         east: None,
         south: Some(Piece::Rail),
@@ -67,6 +75,7 @@ pub const DIE_PATTERNS: [DiePattern; 34] = [
     },
     DiePattern {
         face: DieFace::StraightRail,
+        index: 8,
         north: Some(Piece::Rail),
         east: None,
         south: Some(Piece::Rail),
@@ -75,6 +84,7 @@ pub const DIE_PATTERNS: [DiePattern; 34] = [
     },
     DiePattern {
         face: DieFace::StraightRail,
+        index: 9,
         north: None,
         east: Some(Piece::Rail),
         south: None,
@@ -84,6 +94,7 @@ pub const DIE_PATTERNS: [DiePattern; 34] = [
     // angle road
     DiePattern {
         face: DieFace::AngleRoad,
+        index: 10,
         north: Some(Piece::Road),
         east: None,
         south: None,
@@ -92,6 +103,7 @@ pub const DIE_PATTERNS: [DiePattern; 34] = [
     },
     DiePattern {
         face: DieFace::AngleRoad,
+        index: 11,
         north: Some(Piece::Road),
         east: Some(Piece::Road),
         south: None,
@@ -100,6 +112,7 @@ pub const DIE_PATTERNS: [DiePattern; 34] = [
     },
     DiePattern {
         face: DieFace::AngleRoad,
+        index: 12,
         north: None,
         east: Some(Piece::Road),
         south: Some(Piece::Road),
@@ -108,6 +121,7 @@ pub const DIE_PATTERNS: [DiePattern; 34] = [
     },
     DiePattern {
         face: DieFace::AngleRoad,
+        index: 13,
         north: None,
         east: None,
         south: Some(Piece::Road),
@@ -117,6 +131,7 @@ pub const DIE_PATTERNS: [DiePattern; 34] = [
     // 3 road
     DiePattern {
         face: DieFace::ThreeRoad,
+        index: 14,
         north: Some(Piece::Road),
         east: Some(Piece::Road),
         south: None,
@@ -125,6 +140,7 @@ pub const DIE_PATTERNS: [DiePattern; 34] = [
     },
     DiePattern {
         face: DieFace::ThreeRoad,
+        index: 15,
         north: Some(Piece::Road),
         east: Some(Piece::Road),
         south: Some(Piece::Road),
@@ -133,6 +149,7 @@ pub const DIE_PATTERNS: [DiePattern; 34] = [
     },
     DiePattern {
         face: DieFace::ThreeRoad,
+        index: 16,
         north: None,
         east: Some(Piece::Road),
         south: Some(Piece::Road),
@@ -141,6 +158,7 @@ pub const DIE_PATTERNS: [DiePattern; 34] = [
     },
     DiePattern {
         face: DieFace::ThreeRoad,
+        index: 17,
         north: Some(Piece::Road),
         east: None,
         south: Some(Piece::Road),
@@ -150,6 +168,7 @@ pub const DIE_PATTERNS: [DiePattern; 34] = [
     // straight road
     DiePattern {
         face: DieFace::StraightRoad,
+        index: 18,
         north: Some(Piece::Road),
         east: None,
         south: Some(Piece::Road),
@@ -158,6 +177,7 @@ pub const DIE_PATTERNS: [DiePattern; 34] = [
     },
     DiePattern {
         face: DieFace::StraightRoad,
+        index: 19,
         north: None,
         east: Some(Piece::Road),
         south: None,
@@ -167,6 +187,7 @@ pub const DIE_PATTERNS: [DiePattern; 34] = [
     // overpass
     DiePattern {
         face: DieFace::Overpass,
+        index: 20,
         north: Some(Piece::Road),
         east: Some(Piece::Rail),
         south: Some(Piece::Road),
@@ -175,6 +196,7 @@ pub const DIE_PATTERNS: [DiePattern; 34] = [
     },
     DiePattern {
         face: DieFace::Overpass,
+        index: 21,
         north: Some(Piece::Rail),
         east: Some(Piece::Road),
         south: Some(Piece::Rail),
@@ -184,6 +206,7 @@ pub const DIE_PATTERNS: [DiePattern; 34] = [
     // straight station
     DiePattern {
         face: DieFace::StraightStation,
+        index: 22,
         north: Some(Piece::Rail),
         east: None,
         south: Some(Piece::Road),
@@ -192,6 +215,7 @@ pub const DIE_PATTERNS: [DiePattern; 34] = [
     },
     DiePattern {
         face: DieFace::StraightStation,
+        index: 23,
         north: None,
         east: Some(Piece::Rail),
         south: None,
@@ -200,6 +224,7 @@ pub const DIE_PATTERNS: [DiePattern; 34] = [
     },
     DiePattern {
         face: DieFace::StraightStation,
+        index: 24,
         north: Some(Piece::Road),
         east: None,
         south: Some(Piece::Rail),
@@ -208,6 +233,7 @@ pub const DIE_PATTERNS: [DiePattern; 34] = [
     },
     DiePattern {
         face: DieFace::StraightStation,
+        index: 25,
         north: None,
         east: Some(Piece::Road),
         south: None,
@@ -217,6 +243,7 @@ pub const DIE_PATTERNS: [DiePattern; 34] = [
     // angle station
     DiePattern {
         face: DieFace::AngleStation,
+        index: 26,
         north: Some(Piece::Rail),
         east: None,
         south: None,
@@ -225,6 +252,7 @@ pub const DIE_PATTERNS: [DiePattern; 34] = [
     },
     DiePattern {
         face: DieFace::AngleStation,
+        index: 27,
         north: Some(Piece::Road),
         east: Some(Piece::Rail),
         south: None,
@@ -233,6 +261,7 @@ pub const DIE_PATTERNS: [DiePattern; 34] = [
     },
     DiePattern {
         face: DieFace::AngleStation,
+        index: 28,
         north: None,
         east: Some(Piece::Road),
         south: Some(Piece::Rail),
@@ -241,6 +270,7 @@ pub const DIE_PATTERNS: [DiePattern; 34] = [
     },
     DiePattern {
         face: DieFace::AngleStation,
+        index: 29,
         north: None,
         east: None,
         south: Some(Piece::Road),
@@ -250,6 +280,7 @@ pub const DIE_PATTERNS: [DiePattern; 34] = [
     // mirrored angle station
     DiePattern {
         face: DieFace::AngleStation,
+        index: 30,
         north: Some(Piece::Road),
         east: None,
         south: None,
@@ -258,6 +289,7 @@ pub const DIE_PATTERNS: [DiePattern; 34] = [
     },
     DiePattern {
         face: DieFace::AngleStation,
+        index: 31,
         north: Some(Piece::Rail),
         east: Some(Piece::Road),
         south: None,
@@ -266,6 +298,7 @@ pub const DIE_PATTERNS: [DiePattern; 34] = [
     },
     DiePattern {
         face: DieFace::AngleStation,
+        index: 32,
         north: None,
         east: Some(Piece::Rail),
         south: Some(Piece::Road),
@@ -274,6 +307,7 @@ pub const DIE_PATTERNS: [DiePattern; 34] = [
     },
     DiePattern {
         face: DieFace::AngleStation,
+        index: 33,
         north: None,
         east: None,
         south: Some(Piece::Rail),
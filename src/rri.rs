@@ -1,10 +1,14 @@
-use anyhow::{bail, Result};
+use crate::expansions::{Blocker, EventRoll, Variant};
+use crate::objectives::ObjectiveCard;
+use crate::scoring::ScoreCard;
+use anyhow::{anyhow, bail, Result};
 
-use rand::Rng;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
 use std::borrow::Borrow;
-use std::collections::HashMap;
+use std::collections::{HashMap, VecDeque};
 
-#[derive(Clone, Copy, Eq, PartialEq)]
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
 pub enum Piece {
     Road,
     Rail,
@@ -12,6 +16,8 @@ pub enum Piece {
 
 pub struct DiePattern {
     pub face: DieFace,
+    // position in DIE_PATTERNS
+    pub index: usize,
     // TODO: we probably need this metadata to make use of the client side svgs.
     // pub rotation: u8,
     // pub reflected: bool,
@@ -32,7 +38,7 @@ impl DiePattern {
     }
 }
 
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
 #[repr(u8)]
 pub enum RegularDieFace {
     AngleRail = 0,
@@ -44,7 +50,18 @@ pub enum RegularDieFace {
     StraightRoad = 5,
 }
 
-#[derive(Clone, Copy)]
+impl RegularDieFace {
+    pub const ALL: [RegularDieFace; 6] = [
+        RegularDieFace::AngleRail,
+        RegularDieFace::ThreeRail,
+        RegularDieFace::StraightRail,
+        RegularDieFace::AngleRoad,
+        RegularDieFace::ThreeRoad,
+        RegularDieFace::StraightRoad,
+    ];
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
 #[repr(u8)]
 pub enum SpecialDieFace {
     Overpass = 6,
@@ -52,7 +69,15 @@ pub enum SpecialDieFace {
     AngleStation = 8,
 }
 
-#[derive(Clone, Copy)]
+impl SpecialDieFace {
+    pub const ALL: [SpecialDieFace; 3] = [
+        SpecialDieFace::Overpass,
+        SpecialDieFace::StraightStation,
+        SpecialDieFace::AngleStation,
+    ];
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
 #[repr(u8)]
 pub enum DieFace {
     AngleRail = 0,
@@ -69,6 +94,18 @@ pub enum DieFace {
 }
 
 impl DieFace {
+    pub const ALL: [DieFace; 9] = [
+        DieFace::AngleRail,
+        DieFace::ThreeRail,
+        DieFace::StraightRail,
+        DieFace::AngleRoad,
+        DieFace::ThreeRoad,
+        DieFace::StraightRoad,
+        DieFace::Overpass,
+        DieFace::StraightStation,
+        DieFace::AngleStation,
+    ];
+
     pub fn routes(&self) -> &'static [DiePattern] {
         let range = match self {
             Self::AngleRail => 0..4,
@@ -87,38 +124,112 @@ impl DieFace {
     }
 }
 
+impl From<RegularDieFace> for DieFace {
+    fn from(face: RegularDieFace) -> Self {
+        DieFace::ALL[face as usize]
+    }
+}
+
+impl From<SpecialDieFace> for DieFace {
+    fn from(face: SpecialDieFace) -> Self {
+        DieFace::ALL[face as usize]
+    }
+}
+
+#[derive(Clone, Copy)]
 pub struct DrawAction {
     pub pattern: &'static DiePattern,
     pub tile: Tile,
 }
 
+impl PartialEq for DrawAction {
+    fn eq(&self, other: &Self) -> bool {
+        self.tile == other.tile && self.pattern.index == other.pattern.index
+    }
+}
+
+impl Eq for DrawAction {}
+
 // TODO:
 // speedup: Replace hashmaps with arrays of options.
 // that would use less memory and spend less time hashing data.
+#[derive(Clone)]
 pub struct GameState {
+    pub variant: Variant,
     pub drawn_routes: Vec<DrawAction>,
     pub open_edges: HashMap<TileEdge, Piece>,
+    // tiles that can never be drawn on: lava, volcanoes and craters.
+    pub blocked_tiles: HashMap<Tile, Blocker>,
+    // the event rolled this round, for the expansion variants.
+    pub event: Option<EventRoll>,
+    // shared objectives in play, and the bonus this board earned from each.
+    pub objectives: Vec<ObjectiveCard>,
     pub dice: Dice,
+    // rounds played so far
+    pub round: usize,
 }
 
+#[derive(Clone, Copy)]
 pub struct Dice {
     pub regular: [RegularDieFace; 3],
     pub special: SpecialDieFace,
 }
 
 impl Dice {
+    pub fn faces(&self) -> [DieFace; 4] {
+        [
+            self.regular[0].into(),
+            self.regular[1].into(),
+            self.regular[2].into(),
+            self.special.into(),
+        ]
+    }
+
     pub fn roll(&mut self) {
+        self.roll_with(&mut rand::thread_rng())
+    }
+
+    pub fn roll_with<R: Rng + ?Sized>(&mut self, rng: &mut R) {
         for i in 0..3 {
             self.regular[i] = {
-                let src = rand::thread_rng().gen_range(0..6_u8);
-                unsafe { std::mem::transmute(src) }
+                let src = rng.gen_range(0..6_u8);
+                unsafe { std::mem::transmute::<u8, RegularDieFace>(src) }
             }
         }
 
         self.special = {
-            let src = rand::thread_rng().gen_range(6..9_u8);
-            unsafe { std::mem::transmute(src) }
+            let src = rng.gen_range(6..9_u8);
+            unsafe { std::mem::transmute::<u8, SpecialDieFace>(src) }
+        }
+    }
+
+    // Every distinct roll, with its probability.
+    // The regular dice are unordered, so rolls of the same faces are merged.
+    pub fn outcomes() -> Vec<(Dice, f64)> {
+        let faces = RegularDieFace::ALL;
+        let mut outcomes = Vec::with_capacity(56 * 3);
+        for a in 0..6 {
+            for b in a..6 {
+                for c in b..6 {
+                    // orderings of the three faces
+                    let orders = if a == c {
+                        1
+                    } else if a == b || b == c {
+                        3
+                    } else {
+                        6
+                    };
+                    for special in SpecialDieFace::ALL {
+                        let dice = Dice {
+                            regular: [faces[a], faces[b], faces[c]],
+                            special,
+                        };
+                        outcomes.push((dice, orders as f64 / (216.0 * 3.0)));
+                    }
+                }
+            }
         }
+        outcomes
     }
 }
 
@@ -127,57 +238,329 @@ pub enum Edit {
     Delete(TileEdge),
 }
 
+impl Default for GameState {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl GameState {
-    pub fn play<Agent: RRIAgent>(&mut self, agent: &mut Agent) {
-        for _ in 0..7 {
-            self.play_round(agent)
+    pub const ROUNDS: usize = 7;
+
+    pub fn play<Agent: RRIAgent + ?Sized>(&mut self, agent: &mut Agent) -> Vec<RoundResult> {
+        (0..Self::ROUNDS).map(|_| self.play_round(agent)).collect()
+    }
+
+    pub fn play_with<Agent: RRIAgent + ?Sized, R: Rng + ?Sized>(
+        &mut self,
+        agent: &mut Agent,
+        rng: &mut R,
+    ) -> Vec<RoundResult> {
+        (0..Self::ROUNDS)
+            .map(|_| self.play_round_with(agent, rng))
+            .collect()
+    }
+    pub fn play_round<Agent: RRIAgent + ?Sized>(&mut self, agent: &mut Agent) -> RoundResult {
+        self.play_round_with(agent, &mut rand::thread_rng())
+    }
+
+    // Rolls the dice and events from the given rng, so a game can be replayed with the same rolls.
+    pub fn play_round_with<Agent: RRIAgent + ?Sized, R: Rng + ?Sized>(
+        &mut self,
+        agent: &mut Agent,
+        rng: &mut R,
+    ) -> RoundResult {
+        self.dice.roll_with(rng);
+        self.event = self.variant.roll_event(rng);
+        if let Some(event) = self.event {
+            self.resolve_event(event, rng);
         }
+        self.play_turn(agent)
     }
-    pub fn play_round<Agent: RRIAgent>(&mut self, agent: &mut Agent) {
-        self.dice.roll();
+
+    // Draws the routes for dice that have already been rolled.
+    // A die that couldn't be drawn anywhere when the turn started is a forced pass,
+    // so the agent isn't told about errors from trying to draw it.
+    pub fn play_turn<Agent: RRIAgent + ?Sized>(&mut self, agent: &mut Agent) -> RoundResult {
+        let unplaceable = self.unplaceable_dice();
         let turn = agent.prompt(self);
+        let mut unused = self.dice.faces().to_vec();
+        let mut result = RoundResult::default();
+        let mut failures = Vec::new();
 
         for route in turn.actions {
-            if let Err(error) = self.apply_route(route) {
-                agent.handle_error(error)
+            let face = route.pattern.face;
+            let Some(index) = unused.iter().position(|f| *f == face) else {
+                agent.handle_error(anyhow!("{:?} was not rolled", face));
+                result.errors += 1;
+                continue;
+            };
+            match self.apply_route(route) {
+                Ok(()) => result.placed.push(unused.swap_remove(index)),
+                Err(error) => failures.push((face, error)),
+            }
+        }
+
+        self.settle_turn(unplaceable, unused, failures, result, |error| {
+            agent.handle_error(error)
+        })
+    }
+
+    pub fn play_round_sequential<Agent: SequentialAgent + ?Sized>(
+        &mut self,
+        agent: &mut Agent,
+    ) -> RoundResult {
+        self.play_round_sequential_with(agent, &mut rand::thread_rng())
+    }
+
+    // Like play_round_with, for an agent that draws one route at a time.
+    pub fn play_round_sequential_with<Agent: SequentialAgent + ?Sized, R: Rng + ?Sized>(
+        &mut self,
+        agent: &mut Agent,
+        rng: &mut R,
+    ) -> RoundResult {
+        self.dice.roll_with(rng);
+        self.event = self.variant.roll_event(rng);
+        if let Some(event) = self.event {
+            self.resolve_event(event, rng);
+        }
+        self.play_turn_sequential(agent)
+    }
+
+    // Like play_turn, but the agent draws one route at a time and sees the board after each.
+    // A die is used up once the agent tries it, whether or not the route fits,
+    // and naming a die that isn't left ends the turn.
+    pub fn play_turn_sequential<Agent: SequentialAgent + ?Sized>(
+        &mut self,
+        agent: &mut Agent,
+    ) -> RoundResult {
+        let unplaceable = self.unplaceable_dice();
+        let mut unused = self.dice.faces().to_vec();
+        let mut offered = unused.clone();
+        let mut result = RoundResult::default();
+        let mut failures = Vec::new();
+
+        while !offered.is_empty() {
+            let Some(route) = agent.place(self, &offered) else {
+                break;
+            };
+            let face = route.pattern.face;
+            let Some(index) = offered.iter().position(|f| *f == face) else {
+                agent.handle_error(anyhow!("{:?} was not rolled", face));
+                result.errors += 1;
+                break;
+            };
+            offered.swap_remove(index);
+            match self.apply_route(route) {
+                Ok(()) => {
+                    let index = unused.iter().position(|f| *f == face).expect("offered");
+                    result.placed.push(unused.swap_remove(index));
+                }
+                Err(error) => failures.push((face, error)),
+            }
+        }
+
+        self.settle_turn(unplaceable, unused, failures, result, |error| {
+            agent.handle_error(error)
+        })
+    }
+
+    // Dice with nowhere to go before any of this turn's routes are drawn.
+    // A die the agent blocks with its own routes could have gone first, so it isn't excused.
+    fn unplaceable_dice(&self) -> Vec<DieFace> {
+        let mut faces = self.dice.faces().to_vec();
+        faces.retain(|face| !self.can_place(*face));
+        faces
+    }
+
+    // Sorts out the dice that weren't drawn, and ends the turn.
+    // A die the agent tried and failed to draw is an error, or excused, but never skipped.
+    fn settle_turn(
+        &mut self,
+        unplaceable: Vec<DieFace>,
+        mut unused: Vec<DieFace>,
+        failures: Vec<(DieFace, anyhow::Error)>,
+        mut result: RoundResult,
+        mut handle_error: impl FnMut(anyhow::Error),
+    ) -> RoundResult {
+        for (face, error) in failures {
+            if let Some(index) = unused.iter().position(|f| *f == face) {
+                unused.swap_remove(index);
+                if unplaceable.contains(&face) {
+                    result.unplaceable.push(face);
+                    continue;
+                }
+            }
+            handle_error(error);
+            result.errors += 1;
+        }
+        for face in unused {
+            if unplaceable.contains(&face) {
+                result.unplaceable.push(face);
+            } else {
+                result.skipped.push(face);
             }
         }
+        self.end_turn();
+        result
+    }
+
+    pub fn end_turn(&mut self) {
+        self.complete_objectives();
+        self.round += 1;
+    }
+
+    pub fn is_over(&self) -> bool {
+        self.round >= Self::ROUNDS
+    }
+
+    // Every legal way to draw the die face on the board as it is now.
+    pub fn legal_actions(&self, face: DieFace) -> Vec<DrawAction> {
+        let mut actions = Vec::new();
+        let mut tiles: Vec<Tile> = self.open_edges.keys().map(|edge| edge.tile).collect();
+        tiles.sort_unstable_by_key(|tile| (tile.y, tile.x));
+        tiles.dedup();
+        for tile in tiles {
+            for pattern in face.routes() {
+                let action = DrawAction { pattern, tile };
+                if self.check_route(&action).is_ok() {
+                    actions.push(action);
+                }
+            }
+        }
+        actions
+    }
+
+    pub fn can_place(&self, face: DieFace) -> bool {
+        !self.legal_actions(face).is_empty()
     }
 
     pub fn score(&self) -> isize {
-        //log::warn!("TODO: implement scoring");
-        0
+        ScoreCard::new(self).total()
     }
 
     pub fn new() -> GameState {
+        Self::with_variant(Variant::Base)
+    }
+
+    pub fn with_variant(variant: Variant) -> GameState {
         Self {
+            variant,
             dice: Dice {
                 regular: [RegularDieFace::AngleRail; 3],
                 special: SpecialDieFace::Overpass,
             },
             drawn_routes: Vec::with_capacity(28),
-            open_edges: HashMap::from([
-                // north exits
-                (TileEdge::new(1, 0, Direction::North), Piece::Road),
-                (TileEdge::new(3, 0, Direction::North), Piece::Rail),
-                (TileEdge::new(5, 0, Direction::North), Piece::Road),
-                // west exits
-                (TileEdge::new(0, 1, Direction::East), Piece::Rail),
-                (TileEdge::new(0, 3, Direction::East), Piece::Road),
-                (TileEdge::new(0, 5, Direction::East), Piece::Rail),
-                // south exits
-                (TileEdge::new(1, 6, Direction::South), Piece::Road),
-                (TileEdge::new(3, 6, Direction::South), Piece::Rail),
-                (TileEdge::new(5, 6, Direction::South), Piece::Road),
-                // east exits
-                (TileEdge::new(6, 1, Direction::East), Piece::Rail),
-                (TileEdge::new(6, 3, Direction::East), Piece::Road),
-                (TileEdge::new(6, 5, Direction::East), Piece::Rail),
-            ]),
-        }
-    }
-
-    pub fn apply_edit(&mut self, edit: Edit) -> () {
+            open_edges: HashMap::from(EXITS),
+            blocked_tiles: HashMap::new(),
+            event: None,
+            objectives: vec![],
+            round: 0,
+        }
+    }
+
+    pub fn route_at(&self, tile: Tile) -> Option<&'static DiePattern> {
+        self.drawn_routes
+            .iter()
+            .find(|action| action.tile == tile)
+            .map(|action| action.pattern)
+    }
+
+    // no route drawn and nothing blocking it.
+    pub fn is_empty(&self, tile: Tile) -> bool {
+        !self.blocked_tiles.contains_key(&tile) && self.route_at(tile).is_none()
+    }
+
+    // Erases a drawn route, leaving the tile empty.
+    pub fn remove_route(&mut self, tile: Tile) -> Option<&'static DiePattern> {
+        let index = self
+            .drawn_routes
+            .iter()
+            .position(|action| action.tile == tile)?;
+        let action = self.drawn_routes.remove(index);
+        self.open_edges = self.expected_open_edges();
+        Some(action.pattern)
+    }
+
+    pub fn block_tile(&mut self, tile: Tile, blocker: Blocker) {
+        self.blocked_tiles.insert(tile, blocker);
+        self.open_edges = self.expected_open_edges();
+    }
+
+    // Checks the incrementally maintained state against the drawn routes.
+    pub fn validate(&self) -> Result<()> {
+        let mut problems = Vec::new();
+        for (i, action) in self.drawn_routes.iter().enumerate() {
+            if self.drawn_routes[..i].iter().any(|a| a.tile == action.tile) {
+                problems.push(format!("Tile {} has two routes", action.tile));
+            }
+            if let Some(blocker) = self.blocked_tiles.get(&action.tile) {
+                problems.push(format!(
+                    "Route at {} is under {}",
+                    action.tile,
+                    blocker.name()
+                ));
+            }
+        }
+
+        let expected = self.expected_open_edges();
+        for (edge, piece) in expected.iter() {
+            match self.open_edges.get(edge) {
+                None => problems.push(format!(
+                    "Missing open edge: {:?} side of {}",
+                    edge.direction, edge.tile
+                )),
+                Some(actual) if actual != piece => problems.push(format!(
+                    "Open edge {:?} side of {} is {:?}, expected {:?}",
+                    edge.direction, edge.tile, actual, piece
+                )),
+                Some(_) => {}
+            }
+        }
+        for edge in self.open_edges.keys() {
+            if !expected.contains_key(edge) {
+                problems.push(format!(
+                    "Stale open edge: {:?} side of {}",
+                    edge.direction, edge.tile
+                ));
+            }
+        }
+
+        if !problems.is_empty() {
+            bail!("Inconsistent game state:\n{}", problems.join("\n"))
+        }
+        Ok(())
+    }
+
+    // Rebuilds the open edges from scratch:
+    // the unused exits and every route end that faces an empty tile.
+    pub fn expected_open_edges(&self) -> HashMap<TileEdge, Piece> {
+        let mut open_edges = HashMap::new();
+        for (edge, piece) in EXITS {
+            if self.is_empty(edge.tile) {
+                open_edges.insert(edge, piece);
+            }
+        }
+        for action in self.drawn_routes.iter() {
+            for direction in Direction::ALL {
+                let Some(piece) = action.pattern.get(direction) else {
+                    continue;
+                };
+                let edge = TileEdge {
+                    tile: action.tile,
+                    direction,
+                };
+                if let Some(adjacent) = edge.adjacent() {
+                    if self.is_empty(adjacent.tile) {
+                        open_edges.insert(adjacent, piece);
+                    }
+                }
+            }
+        }
+        open_edges
+    }
+
+    pub fn apply_edit(&mut self, edit: Edit) {
         match edit {
             Edit::Add(edge, piece) => {
                 //
@@ -190,50 +573,173 @@ impl GameState {
         }
     }
     pub fn apply_route(&mut self, action: DrawAction) -> Result<()> {
-        let DrawAction { pattern, tile } = action;
+        let edits = self.check_route(&action)?;
+
+        // commit edits to state
+        for edit in edits {
+            self.apply_edit(edit);
+        }
+        self.drawn_routes.push(action);
+        Ok(())
+    }
+
+    // Dry run of drawing a route. The edits are only valid until the state changes.
+    pub fn check_route(&self, action: &DrawAction) -> Result<Vec<Edit>> {
+        let DrawAction { pattern, tile } = *action;
 
         if self.drawn_routes.iter().any(|action| action.tile == tile) {
             bail!("Cannot draw over existing route")
         }
+        if let Some(blocker) = self.blocked_tiles.get(&tile) {
+            bail!("Cannot draw over {}", blocker.name())
+        }
 
         // dry run of edits
         let mut edits: Vec<Edit> = Vec::with_capacity(4);
+        let mut connected = false;
         for direction in Direction::ALL {
             let edge = TileEdge { tile, direction };
             match (pattern.get(direction), self.open_edges.get(&edge)) {
                 (Some(piece1), Some(piece2)) => {
                     if piece1 == *piece2 {
+                        connected = true;
                         edits.push(Edit::Delete(edge));
                     } else {
                         bail!("Cannot connect railway directly to roadway")
                     }
                 }
                 (Some(piece), None) => {
+                    // route ends facing a filled tile stay dead ends
                     if let Some(edge) = edge.adjacent() {
-                        edits.push(Edit::Add(edge, piece));
+                        if self.is_empty(edge.tile) {
+                            edits.push(Edit::Add(edge, piece));
+                        }
                     }
                 }
-                (None, _) => {}
+                // the tile is filled now, so nothing else can connect here
+                (None, Some(_)) => edits.push(Edit::Delete(edge)),
+                (None, None) => {}
             }
         }
-        if !edits.iter().any(|e| matches!(e, Edit::Delete(_))) {
+        if !connected {
             bail!("Route doesn't connect to any road or rail in your network.")
         }
-
-        // commit edits to state
-        for edit in edits {
-            self.apply_edit(edit);
-        }
-        self.drawn_routes.push(DrawAction { tile, pattern });
-        Ok(())
+        Ok(edits)
     }
 }
 
+// What happened to each die rolled this round.
+#[derive(Default, Debug, Clone, PartialEq, Eq)]
+pub struct RoundResult {
+    pub placed: Vec<DieFace>,
+    // dice with no legal placement when the turn started, the player is allowed to pass them.
+    pub unplaceable: Vec<DieFace>,
+    // dice the agent could have drawn, but didn't try to.
+    pub skipped: Vec<DieFace>,
+    // errors passed on to the agent
+    pub errors: usize,
+}
+
 pub struct Turn {
     pub actions: Vec<DrawAction>,
 }
 
-#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+// Several boards playing the same dice, and racing for the same objectives.
+pub struct Match {
+    pub boards: Vec<GameState>,
+}
+
+impl Match {
+    // Claims are shared by position, so every board must race for the same objectives.
+    pub fn new(boards: Vec<GameState>) -> Self {
+        assert!(!boards.is_empty(), "a match needs at least one board");
+        let names = |board: &GameState| -> Vec<&'static str> {
+            board
+                .objectives
+                .iter()
+                .map(|card| card.objective.name())
+                .collect()
+        };
+        assert!(
+            boards.iter().all(|board| names(board) == names(&boards[0])),
+            "every board in a match must have the same objectives"
+        );
+        Self { boards }
+    }
+
+    pub fn play(&mut self, agents: &mut [&mut dyn RRIAgent]) {
+        for _ in 0..GameState::ROUNDS {
+            self.play_round(agents);
+        }
+    }
+
+    pub fn play_round(&mut self, agents: &mut [&mut dyn RRIAgent]) -> Vec<RoundResult> {
+        self.play_round_with(agents, &mut rand::thread_rng())
+    }
+
+    // One agent per board, every board gets the same rolls and the same event.
+    pub fn play_round_with<R: Rng + ?Sized>(
+        &mut self,
+        agents: &mut [&mut dyn RRIAgent],
+        rng: &mut R,
+    ) -> Vec<RoundResult> {
+        assert_eq!(
+            agents.len(),
+            self.boards.len(),
+            "a match needs one agent per board"
+        );
+        let mut dice = self.boards[0].dice;
+        dice.roll_with(rng);
+        let event = self.boards[0].variant.roll_event(rng);
+        // where lava flows is drawn once, so boards that match get the same lava
+        let seed = rng.gen();
+
+        let mut results = Vec::with_capacity(self.boards.len());
+        for (board, agent) in self.boards.iter_mut().zip(agents.iter_mut()) {
+            board.dice = dice;
+            board.event = event;
+            if let Some(event) = event {
+                board.resolve_event(event, &mut StdRng::seed_from_u64(seed));
+            }
+            results.push(board.play_turn(*agent));
+        }
+        self.share_claims();
+        results
+    }
+
+    // Boards that completed an objective this round all earned the full bonus.
+    // From the next round on, it only earns the reduced bonus.
+    pub fn share_claims(&mut self) {
+        for i in 0..self.boards[0].objectives.len() {
+            if self.boards.iter().any(|board| board.objectives[i].claimed) {
+                for board in self.boards.iter_mut() {
+                    board.objectives[i].claimed = true;
+                }
+            }
+        }
+    }
+}
+
+pub const EXITS: [(TileEdge, Piece); 12] = [
+    // north exits
+    (TileEdge::new(1, 0, Direction::North), Piece::Road),
+    (TileEdge::new(3, 0, Direction::North), Piece::Rail),
+    (TileEdge::new(5, 0, Direction::North), Piece::Road),
+    // west exits
+    (TileEdge::new(0, 1, Direction::West), Piece::Rail),
+    (TileEdge::new(0, 3, Direction::West), Piece::Road),
+    (TileEdge::new(0, 5, Direction::West), Piece::Rail),
+    // south exits
+    (TileEdge::new(1, 6, Direction::South), Piece::Road),
+    (TileEdge::new(3, 6, Direction::South), Piece::Rail),
+    (TileEdge::new(5, 6, Direction::South), Piece::Road),
+    // east exits
+    (TileEdge::new(6, 1, Direction::East), Piece::Rail),
+    (TileEdge::new(6, 3, Direction::East), Piece::Road),
+    (TileEdge::new(6, 5, Direction::East), Piece::Rail),
+];
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
 #[repr(u8)]
 pub enum Direction {
     North,
@@ -249,22 +755,64 @@ impl Direction {
         Direction::South,
         Direction::West,
     ];
+
+    pub fn opposite(self) -> Direction {
+        match self {
+            Direction::North => Direction::South,
+            Direction::East => Direction::West,
+            Direction::South => Direction::North,
+            Direction::West => Direction::East,
+        }
+    }
+
+    // quarter turn clockwise
+    pub fn rotate(self) -> Direction {
+        match self {
+            Direction::North => Direction::East,
+            Direction::East => Direction::South,
+            Direction::South => Direction::West,
+            Direction::West => Direction::North,
+        }
+    }
 }
 
-#[derive(PartialEq, Eq, Hash, Clone, Copy)]
+#[derive(PartialEq, Eq, Hash, Clone, Copy, Debug)]
 pub struct Tile {
     pub x: u8,
     pub y: u8,
 }
 
-#[derive(PartialEq, Eq, Hash)]
+impl std::fmt::Display for Tile {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "({}, {})", self.x, self.y)
+    }
+}
+
+impl Tile {
+    pub const CENTER: Tile = Tile { x: 3, y: 3 };
+
+    pub fn all() -> impl Iterator<Item = Tile> {
+        (0..7).flat_map(|y| (0..7).map(move |x| Tile { x, y }))
+    }
+
+    pub fn neighbor(self, direction: Direction) -> Option<Tile> {
+        TileEdge {
+            tile: self,
+            direction,
+        }
+        .adjacent()
+        .map(|edge| edge.tile)
+    }
+}
+
+#[derive(PartialEq, Eq, Hash, Clone, Copy, Debug)]
 pub struct TileEdge {
     pub tile: Tile,
     pub direction: Direction,
 }
 
 impl TileEdge {
-    pub fn new(x: u8, y: u8, direction: Direction) -> Self {
+    pub const fn new(x: u8, y: u8, direction: Direction) -> Self {
         TileEdge {
             tile: Tile { x, y },
             direction,
@@ -301,5 +849,328 @@ impl TileEdge {
 
 pub trait RRIAgent {
     fn prompt(&mut self, state: &GameState) -> Turn;
+
+    // Agents that only choose legal actions never see an error, so one is a bug in the engine.
+    fn handle_error(&mut self, error: anyhow::Error) {
+        log::error!("{}: {}", std::any::type_name::<Self>(), error);
+    }
+}
+
+// Asked for one route at a time, so it can react to the routes it has already drawn.
+pub trait SequentialAgent {
+    // The state includes this turn's routes so far, and dice are the ones still unused.
+    // None passes the rest of them.
+    fn place(&mut self, state: &GameState, dice: &[DieFace]) -> Option<DrawAction>;
     fn handle_error(&mut self, error: anyhow::Error);
 }
+
+// Plays a whole turn agent one route at a time.
+// It's prompted at the start of each turn, and its routes are handed out in order.
+pub struct WholeTurn<A: RRIAgent> {
+    pub agent: A,
+    queued: VecDeque<DrawAction>,
+    // the board the next queued route was planned for, any other board gets a new turn
+    expected: Option<GameState>,
+}
+
+impl<A: RRIAgent> WholeTurn<A> {
+    pub fn new(agent: A) -> Self {
+        Self {
+            agent,
+            queued: VecDeque::new(),
+            expected: None,
+        }
+    }
+}
+
+impl<A: RRIAgent> SequentialAgent for WholeTurn<A> {
+    fn place(&mut self, state: &GameState, dice: &[DieFace]) -> Option<DrawAction> {
+        let planned = self.expected.as_ref().is_some_and(|expected| {
+            expected.round == state.round
+                && expected.dice.faces() == state.dice.faces()
+                && expected.drawn_routes == state.drawn_routes
+                && expected.blocked_tiles == state.blocked_tiles
+        });
+        if !planned {
+            // dice already used this turn are still on the state, so their routes are dropped
+            let mut offered = dice.to_vec();
+            self.queued = self
+                .agent
+                .prompt(state)
+                .actions
+                .into_iter()
+                .filter(|route| {
+                    let index = offered.iter().position(|f| *f == route.pattern.face);
+                    index.map(|index| offered.swap_remove(index)).is_some()
+                })
+                .collect();
+            self.expected = Some(state.clone());
+        }
+        let route = self.queued.pop_front()?;
+        // The rest of the turn was planned on this route fitting. It doesn't, so the engine
+        // passes the error on, and the next route is planned again from the board it leaves.
+        if self
+            .expected
+            .as_mut()
+            .expect("planned")
+            .apply_route(route)
+            .is_err()
+        {
+            self.queued.clear();
+            self.expected = None;
+        }
+        Some(route)
+    }
+
+    fn handle_error(&mut self, error: anyhow::Error) {
+        self.agent.handle_error(error)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::agent::RandomAgent;
+    use proptest::prelude::*;
+
+    // Tries to draw every die on the first exit.
+    struct Stubborn {
+        errors: usize,
+    }
+
+    impl RRIAgent for Stubborn {
+        fn prompt(&mut self, state: &GameState) -> Turn {
+            Turn {
+                actions: state
+                    .dice
+                    .faces()
+                    .iter()
+                    .map(|face| DrawAction {
+                        pattern: &face.routes()[0],
+                        tile: EXITS[0].0.tile,
+                    })
+                    .collect(),
+            }
+        }
+
+        fn handle_error(&mut self, _error: anyhow::Error) {
+            self.errors += 1;
+        }
+    }
+
+    proptest! {
+        // Plays random legal routes, and checks the invariants after every one.
+        #[test]
+        fn test_random_games_stay_consistent(
+            steps in proptest::collection::vec((0..9_usize, any::<prop::sample::Index>()), 0..40)
+        ) {
+            let mut state = GameState::new();
+            for (face, choice) in steps {
+                let actions = state.legal_actions(DieFace::ALL[face]);
+                if actions.is_empty() {
+                    continue;
+                }
+                state.apply_route(*choice.get(&actions)).unwrap();
+                if let Err(error) = state.validate() {
+                    prop_assert!(false, "{}", error);
+                }
+            }
+        }
+    }
+
+    // Draws the first legal route for any die, so it only works if it sees the new routes.
+    struct FirstFit {
+        routes_seen: Vec<usize>,
+    }
+
+    impl SequentialAgent for FirstFit {
+        fn place(&mut self, state: &GameState, dice: &[DieFace]) -> Option<DrawAction> {
+            self.routes_seen.push(state.drawn_routes.len());
+            dice.iter()
+                .find_map(|face| state.legal_actions(*face).first().copied())
+        }
+
+        fn handle_error(&mut self, error: anyhow::Error) {
+            panic!("{}", error);
+        }
+    }
+
+    #[test]
+    fn test_sequential_agent_sees_each_route() {
+        let mut state = GameState::new();
+        let mut agent = FirstFit {
+            routes_seen: vec![],
+        };
+        let result = state.play_round_sequential(&mut agent);
+
+        assert_eq!(result.placed.len(), 4);
+        assert_eq!(agent.routes_seen, vec![0, 1, 2, 3]);
+    }
+
+    #[test]
+    fn test_whole_turn_adapter_plays_the_same_turn() {
+        let dice = Dice {
+            regular: [
+                RegularDieFace::AngleRoad,
+                RegularDieFace::ThreeRail,
+                RegularDieFace::StraightRail,
+            ],
+            special: SpecialDieFace::AngleStation,
+        };
+        let mut whole = GameState::new();
+        whole.dice = dice;
+        let mut sequential = whole.clone();
+
+        let expected = whole.play_turn(&mut RandomAgent::seeded(5));
+        let result = sequential.play_turn_sequential(&mut WholeTurn::new(RandomAgent::seeded(5)));
+        assert_eq!(result, expected);
+        assert!(sequential.drawn_routes == whole.drawn_routes);
+    }
+
+    #[test]
+    fn test_whole_turn_adapter_replans_for_a_new_game() {
+        let mut agent = WholeTurn::new(RandomAgent::seeded(5));
+        let mut abandoned = GameState::new();
+        abandoned.dice = Dice {
+            regular: [RegularDieFace::StraightRail; 3],
+            special: SpecialDieFace::StraightStation,
+        };
+        agent.place(&abandoned, &abandoned.dice.faces()).unwrap();
+
+        // same round, but none of the queued routes are for these dice
+        let mut game = GameState::new();
+        game.dice = Dice {
+            regular: [RegularDieFace::AngleRoad; 3],
+            special: SpecialDieFace::AngleStation,
+        };
+        let result = game.play_turn_sequential(&mut agent);
+        assert_eq!(result.errors, 0);
+        assert_eq!(result.placed.len(), 4);
+    }
+
+    #[test]
+    fn test_identical_match_boards_get_the_same_lava() {
+        let board = || GameState::with_variant(Variant::Lava);
+        let mut game = Match::new(vec![board(), board()]);
+        let mut first = RandomAgent::seeded(4);
+        let mut second = RandomAgent::seeded(4);
+        let mut rng = StdRng::seed_from_u64(6);
+        for _ in 0..GameState::ROUNDS {
+            game.play_round_with(&mut [&mut first, &mut second], &mut rng);
+            assert_eq!(game.boards[0].blocked_tiles, game.boards[1].blocked_tiles);
+            assert!(game.boards[0].drawn_routes == game.boards[1].drawn_routes);
+        }
+        assert!(!game.boards[0].blocked_tiles.is_empty());
+    }
+
+    #[test]
+    fn test_patterns_know_their_index() {
+        for (i, pattern) in crate::routes::DIE_PATTERNS.iter().enumerate() {
+            assert_eq!(pattern.index, i);
+        }
+    }
+
+    #[test]
+    fn test_unplaceable_dice_are_not_penalized() {
+        let mut state = GameState::new();
+        for (edge, _) in EXITS {
+            state.block_tile(edge.tile, Blocker::Crater);
+        }
+        let mut agent = Stubborn { errors: 0 };
+        let result = state.play_round(&mut agent);
+
+        assert_eq!(result.unplaceable.len(), 4);
+        assert!(result.placed.is_empty());
+        assert_eq!(result.errors, 0);
+        assert_eq!(agent.errors, 0);
+    }
+
+    // Draws the given routes, whatever the board.
+    struct Scripted(Vec<DrawAction>);
+
+    impl RRIAgent for Scripted {
+        fn prompt(&mut self, _state: &GameState) -> Turn {
+            Turn {
+                actions: self.0.clone(),
+            }
+        }
+    }
+
+    // A new plan each time it's prompted, then nothing.
+    struct Plans(Vec<Vec<DrawAction>>);
+
+    impl RRIAgent for Plans {
+        fn prompt(&mut self, _state: &GameState) -> Turn {
+            Turn {
+                actions: if self.0.is_empty() {
+                    vec![]
+                } else {
+                    self.0.remove(0)
+                },
+            }
+        }
+    }
+
+    fn straight_rails() -> Dice {
+        Dice {
+            regular: [RegularDieFace::StraightRail; 3],
+            special: SpecialDieFace::StraightStation,
+        }
+    }
+
+    #[test]
+    fn test_dice_blocked_by_earlier_routes_are_skipped() {
+        // the north rail exit is the only way in, and it leads nowhere
+        let mut state = GameState::new();
+        for (edge, _) in EXITS
+            .iter()
+            .filter(|(edge, _)| edge.tile != Tile { x: 3, y: 0 })
+        {
+            state.block_tile(edge.tile, Blocker::Crater);
+        }
+        for tile in [
+            Tile { x: 2, y: 0 },
+            Tile { x: 4, y: 0 },
+            Tile { x: 3, y: 1 },
+        ] {
+            state.block_tile(tile, Blocker::Crater);
+        }
+        state.dice = straight_rails();
+        let only = state.legal_actions(DieFace::StraightRail)[0];
+
+        let result = state.play_turn(&mut Scripted(vec![only]));
+        assert_eq!(result.placed, vec![DieFace::StraightRail]);
+        assert!(result.unplaceable.is_empty());
+        assert_eq!(result.skipped.len(), 3);
+    }
+
+    #[test]
+    fn test_failed_die_is_not_also_skipped() {
+        let mut state = GameState::new();
+        state.dice = straight_rails();
+        let nowhere = DrawAction {
+            pattern: &DieFace::StraightRail.routes()[0],
+            tile: Tile::CENTER,
+        };
+
+        let result = state.play_turn(&mut Scripted(vec![nowhere]));
+        assert_eq!(result.errors, 1);
+        assert_eq!(result.skipped.len(), 3);
+    }
+
+    #[test]
+    fn test_whole_turn_adapter_replans_after_a_failed_route() {
+        let mut game = GameState::new();
+        game.dice = straight_rails();
+        let nowhere = DrawAction {
+            pattern: &DieFace::StraightRail.routes()[0],
+            tile: Tile::CENTER,
+        };
+        let fits = game.legal_actions(DieFace::StraightRail)[0];
+        let mut agent = WholeTurn::new(Plans(vec![vec![nowhere], vec![fits]]));
+
+        let result = game.play_turn_sequential(&mut agent);
+        assert_eq!(result.errors, 1);
+        assert_eq!(result.placed, vec![DieFace::StraightRail]);
+    }
+}
@@ -0,0 +1,229 @@
+use crate::expansions::Blocker;
+use crate::rri::{DieFace, DiePattern, Direction, GameState, Piece, Tile, EXITS};
+use std::collections::HashMap;
+
+// points for a single network connecting n exits
+const EXIT_POINTS: [isize; 13] = [0, 0, 4, 8, 12, 16, 20, 24, 28, 32, 36, 40, 45];
+pub const LAVA_LAKE_POINTS: isize = 5;
+pub const CRATER_POINTS: isize = 2;
+
+#[derive(Default, Debug, Clone, PartialEq, Eq)]
+pub struct ScoreCard {
+    pub exits: isize,
+    pub longest_rail: isize,
+    pub longest_road: isize,
+    pub center: isize,
+    // count of route ends leading nowhere, these are subtracted.
+    pub errors: isize,
+    pub lava: isize,
+    pub craters: isize,
+    pub objectives: isize,
+}
+
+impl ScoreCard {
+    pub fn new(state: &GameState) -> Self {
+        let board = Board::new(state);
+        Self {
+            exits: board.exits(),
+            longest_rail: board.longest(Piece::Rail),
+            longest_road: board.longest(Piece::Road),
+            center: board.center(),
+            errors: board.errors(state),
+            lava: LAVA_LAKE_POINTS * state.contained_lava_lakes() as isize,
+            craters: CRATER_POINTS * state.mined_craters() as isize,
+            objectives: state.objectives.iter().filter_map(|card| card.bonus).sum(),
+        }
+    }
+
+    pub fn total(&self) -> isize {
+        self.exits + self.longest_rail + self.longest_road + self.center - self.errors
+            + self.lava
+            + self.craters
+            + self.objectives
+    }
+}
+
+// A segment is a piece of a route that is connected within its tile.
+// Only the overpass has two segments, every other route is fully connected.
+type Segment = (Tile, u8);
+
+fn segment(pattern: &DiePattern, direction: Direction) -> u8 {
+    match (pattern.face, direction) {
+        (DieFace::Overpass, Direction::East | Direction::West) => 1,
+        _ => 0,
+    }
+}
+
+pub(crate) struct Board {
+    routes: HashMap<Tile, &'static DiePattern>,
+}
+
+impl Board {
+    pub(crate) fn new(state: &GameState) -> Self {
+        Self {
+            routes: state
+                .drawn_routes
+                .iter()
+                .map(|action| (action.tile, action.pattern))
+                .collect(),
+        }
+    }
+
+    // The segment across the edge, if the pieces on both sides line up.
+    fn connected(&self, tile: Tile, direction: Direction, piece: Piece) -> Option<Segment> {
+        let neighbor = tile.neighbor(direction)?;
+        let route = self.routes.get(&neighbor)?;
+        if route.get(direction.opposite()) == Some(piece) {
+            Some((neighbor, segment(route, direction.opposite())))
+        } else {
+            None
+        }
+    }
+
+    fn exits(&self) -> isize {
+        self.exit_networks()
+            .iter()
+            .map(|exits| EXIT_POINTS[exits.len()])
+            .sum()
+    }
+
+    // Indices into EXITS, grouped by the network connecting them.
+    pub(crate) fn exit_networks(&self) -> Vec<Vec<usize>> {
+        let mut networks: HashMap<Segment, Segment> = HashMap::new();
+        fn find(networks: &mut HashMap<Segment, Segment>, segment: Segment) -> Segment {
+            let parent = *networks.entry(segment).or_insert(segment);
+            if parent == segment {
+                return segment;
+            }
+            let root = find(networks, parent);
+            networks.insert(segment, root);
+            root
+        }
+
+        for (tile, route) in self.routes.iter() {
+            for direction in Direction::ALL {
+                let Some(piece) = route.get(direction) else {
+                    continue;
+                };
+                let a = find(&mut networks, (*tile, segment(route, direction)));
+                if let Some(other) = self.connected(*tile, direction, piece) {
+                    let b = find(&mut networks, other);
+                    networks.insert(a, b);
+                }
+            }
+        }
+
+        let mut exits: HashMap<Segment, Vec<usize>> = HashMap::new();
+        for (i, (edge, piece)) in EXITS.into_iter().enumerate() {
+            let Some(route) = self.routes.get(&edge.tile) else {
+                continue;
+            };
+            if route.get(edge.direction) == Some(piece) {
+                let root = find(&mut networks, (edge.tile, segment(route, edge.direction)));
+                exits.entry(root).or_default().push(i);
+            }
+        }
+        exits.into_values().collect()
+    }
+
+    // Number of routes along the longest unbroken path of a single piece.
+    pub(crate) fn longest(&self, piece: Piece) -> isize {
+        let mut longest = 0;
+        let mut path = Vec::new();
+        for (tile, route) in self.routes.iter() {
+            let mut segments: Vec<u8> = Direction::ALL
+                .into_iter()
+                .filter(|direction| route.get(*direction) == Some(piece))
+                .map(|direction| segment(route, direction))
+                .collect();
+            segments.dedup();
+            for index in segments {
+                path.push((*tile, index));
+                longest = longest.max(self.extend(piece, &mut path));
+                path.pop();
+            }
+        }
+        longest as isize
+    }
+
+    // depth first search over simple paths
+    fn extend(&self, piece: Piece, path: &mut Vec<Segment>) -> usize {
+        let (tile, index) = *path.last().expect("path starts with a segment");
+        let route = self.routes[&tile];
+        let mut longest = path.len();
+        for direction in Direction::ALL {
+            if route.get(direction) != Some(piece) || segment(route, direction) != index {
+                continue;
+            }
+            if let Some(next) = self.connected(tile, direction, piece) {
+                if !path.contains(&next) {
+                    path.push(next);
+                    longest = longest.max(self.extend(piece, path));
+                    path.pop();
+                }
+            }
+        }
+        longest
+    }
+
+    fn center(&self) -> isize {
+        self.routes
+            .keys()
+            .filter(|tile| (2..=4).contains(&tile.x) && (2..=4).contains(&tile.y))
+            .count() as isize
+    }
+
+    // Route ends that face an empty tile, lava, or a route without a matching piece.
+    // The edge of the board, exits and craters don't count.
+    fn errors(&self, state: &GameState) -> isize {
+        let mut errors = 0;
+        for (tile, route) in self.routes.iter() {
+            for direction in Direction::ALL {
+                let Some(piece) = route.get(direction) else {
+                    continue;
+                };
+                let Some(neighbor) = tile.neighbor(direction) else {
+                    continue;
+                };
+                if state.blocked_tiles.get(&neighbor) == Some(&Blocker::Crater) {
+                    continue;
+                }
+                if self.connected(*tile, direction, piece).is_none() {
+                    errors += 1;
+                }
+            }
+        }
+        errors
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::routes::DIE_PATTERNS;
+    use crate::rri::DrawAction;
+
+    #[test]
+    fn test_straight_rail_across_board() {
+        let mut state = GameState::new();
+        for y in 0..7 {
+            state
+                .apply_route(DrawAction {
+                    pattern: &DIE_PATTERNS[8],
+                    tile: Tile { x: 3, y },
+                })
+                .unwrap();
+        }
+        let card = ScoreCard::new(&state);
+        assert_eq!(
+            card,
+            ScoreCard {
+                exits: 4,
+                longest_rail: 7,
+                center: 3,
+                ..Default::default()
+            }
+        );
+        assert_eq!(state.score(), 14);
+    }
+}
@@ -6,10 +6,12 @@ use axum::response::Html;
 pub struct GameTemplate;
 
 pub trait MyTemplate {
+    #[allow(clippy::result_large_err)]
     fn to_html(&self) -> axum::response::Result<Html<String>>;
 }
 
 impl<T: Template> MyTemplate for T {
+    #[allow(clippy::result_large_err)]
     fn to_html(&self) -> axum::response::Result<Html<String>> {
         match self.render() {
             Ok(html) => Ok(Html(html)),
@@ -18,7 +20,7 @@ impl<T: Template> MyTemplate for T {
     }
 }
 
-pub const DAISY_THEMES: [&'static str; 30] = [
+pub const DAISY_THEMES: [&str; 30] = [
     "light",
     "dark",
     "cupcake",
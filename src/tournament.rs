@@ -0,0 +1,205 @@
+use crate::rri::{GameState, RRIAgent};
+use anyhow::Result;
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+use serde::Serialize;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::Path;
+
+// width of a score histogram bucket, in points
+const BUCKET: isize = 10;
+
+// Plays every agent through the same seeded dice, so their scores can be compared game by game.
+// Games are on the base board.
+pub struct Tournament {
+    pub games: usize,
+    // game i rolls its dice from seed + i
+    pub seed: u64,
+}
+
+#[derive(Serialize)]
+pub struct TournamentReport {
+    pub games: usize,
+    pub seed: u64,
+    pub agents: Vec<AgentReport>,
+    pub pairs: Vec<PairReport>,
+}
+
+#[derive(Serialize)]
+pub struct AgentReport {
+    pub name: String,
+    pub scores: Vec<isize>,
+    pub mean: f64,
+    pub median: f64,
+    pub std_dev: f64,
+    pub histogram: Vec<Bucket>,
+    // errors per round played
+    pub error_rate: f64,
+}
+
+#[derive(Serialize, Debug, PartialEq, Eq)]
+pub struct Bucket {
+    // lowest score in the bucket
+    pub from: isize,
+    pub count: usize,
+}
+
+// Head to head results over the same rolls, from the first agent's side.
+#[derive(Serialize)]
+pub struct PairReport {
+    pub agent: String,
+    pub opponent: String,
+    pub wins: usize,
+    pub losses: usize,
+    pub draws: usize,
+}
+
+impl Tournament {
+    pub fn new(games: usize, seed: u64) -> Self {
+        Self { games, seed }
+    }
+
+    pub fn run(&self, entrants: &mut [(&str, &mut dyn RRIAgent)]) -> TournamentReport {
+        let mut scores = vec![Vec::with_capacity(self.games); entrants.len()];
+        let mut errors = vec![0; entrants.len()];
+        for game in 0..self.games {
+            for (i, (name, agent)) in entrants.iter_mut().enumerate() {
+                let mut rng = StdRng::seed_from_u64(self.seed + game as u64);
+                let mut state = GameState::new();
+                for _ in 0..GameState::ROUNDS {
+                    errors[i] += state.play_round_with(*agent, &mut rng).errors;
+                }
+                log::debug!("Game {}: {} scored {}", game, name, state.score());
+                scores[i].push(state.score());
+            }
+        }
+
+        let rounds = (self.games * GameState::ROUNDS).max(1) as f64;
+        let mut pairs = Vec::new();
+        for a in 0..entrants.len() {
+            for b in a + 1..entrants.len() {
+                let games = scores[a].iter().zip(scores[b].iter());
+                pairs.push(PairReport {
+                    agent: entrants[a].0.to_string(),
+                    opponent: entrants[b].0.to_string(),
+                    wins: games.clone().filter(|(a, b)| a > b).count(),
+                    losses: games.clone().filter(|(a, b)| a < b).count(),
+                    draws: games.filter(|(a, b)| a == b).count(),
+                });
+            }
+        }
+        let agents = entrants
+            .iter()
+            .zip(scores)
+            .zip(errors)
+            .map(|(((name, _), scores), errors)| {
+                AgentReport::new(name, scores, errors as f64 / rounds)
+            })
+            .collect();
+        TournamentReport {
+            games: self.games,
+            seed: self.seed,
+            agents,
+            pairs,
+        }
+    }
+}
+
+impl AgentReport {
+    fn new(name: &str, scores: Vec<isize>, error_rate: f64) -> Self {
+        let mut sorted = scores.clone();
+        sorted.sort_unstable();
+        let n = sorted.len().max(1) as f64;
+        let mean = sorted.iter().sum::<isize>() as f64 / n;
+        let median = match sorted.len() {
+            0 => 0.0,
+            len if len % 2 == 0 => (sorted[len / 2 - 1] + sorted[len / 2]) as f64 / 2.0,
+            len => sorted[len / 2] as f64,
+        };
+        let variance = sorted
+            .iter()
+            .map(|score| (*score as f64 - mean).powi(2))
+            .sum::<f64>()
+            / n;
+
+        let mut histogram: Vec<Bucket> = Vec::new();
+        for score in sorted.iter() {
+            let from = score.div_euclid(BUCKET) * BUCKET;
+            match histogram.last_mut() {
+                Some(bucket) if bucket.from == from => bucket.count += 1,
+                _ => histogram.push(Bucket { from, count: 1 }),
+            }
+        }
+
+        Self {
+            name: name.to_string(),
+            scores,
+            mean,
+            median,
+            std_dev: variance.sqrt(),
+            histogram,
+            error_rate,
+        }
+    }
+}
+
+impl TournamentReport {
+    pub fn write_json(&self, path: impl AsRef<Path>) -> Result<()> {
+        fs::write(path, serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+
+    // Appends a row per agent, so runs can be tracked over time in one file.
+    pub fn write_csv(&self, path: impl AsRef<Path>) -> Result<()> {
+        let path = path.as_ref();
+        let new = !path.exists();
+        let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+        if new {
+            writeln!(file, "agent,games,seed,mean,median,std_dev,error_rate")?;
+        }
+        for agent in self.agents.iter() {
+            writeln!(
+                file,
+                "{},{},{},{:.2},{:.1},{:.2},{:.4}",
+                agent.name,
+                self.games,
+                self.seed,
+                agent.mean,
+                agent.median,
+                agent.std_dev,
+                agent.error_rate
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::agent::RandomAgent;
+
+    #[test]
+    fn test_agents_play_the_same_rolls() {
+        let mut a = RandomAgent::seeded(1);
+        let mut b = RandomAgent::seeded(1);
+        let report = Tournament::new(3, 42).run(&mut [("a", &mut a), ("b", &mut b)]);
+
+        assert_eq!(report.agents[0].scores, report.agents[1].scores);
+        assert_eq!(report.pairs[0].draws, 3);
+        assert_eq!(report.agents[0].error_rate, 0.0);
+    }
+
+    #[test]
+    fn test_summary_statistics() {
+        let report = AgentReport::new("a", vec![12, 4, 8, 15], 0.0);
+        assert_eq!(report.mean, 9.75);
+        assert_eq!(report.median, 10.0);
+        assert!((report.std_dev - 4.1458).abs() < 1e-4);
+        assert_eq!(
+            report.histogram,
+            vec![Bucket { from: 0, count: 2 }, Bucket { from: 10, count: 2 }]
+        );
+    }
+}